@@ -0,0 +1,346 @@
+use super::*;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+/// An inclusive range of characters labelling a transition, a single character being the range
+/// from itself to itself.
+///
+/// The alphabet of an automaton is a set of ranges, so that a character class such as `[^x]`
+/// or `.` takes a couple of transitions instead of one per Unicode character. The alphabet of a
+/// [`DFA`] has to be made of disjoint ranges; an [`EpsilonNFA`] may use overlapping ones, which
+/// [`EpsilonNFA::to_dfa`] splits into disjoint atoms. Both convert from `char` and from
+/// `RangeInclusive<char>`, so the automata can still be built from plain characters.
+///
+/// ```
+/// use re_rs::CharRange;
+///
+/// let digits = CharRange::from('0'..='9');
+/// assert!(digits.contains('5') && !digits.contains('a'));
+/// assert_eq!(digits.to_string(), "0-9");
+/// assert_eq!(CharRange::from('a').to_string(), "a");
+/// assert_eq!(CharRange::from('\0'..=char::MAX).to_string(), "\\0-\\u{10ffff}");
+/// assert_eq!(CharRange::from('\u{d7ff}'..='\u{e000}').chars().count(), 2);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CharRange {
+    start: char,
+    end: char,
+}
+
+impl CharRange {
+    /// # Panics
+    ///
+    /// Panics if `end` comes before `start`.
+    pub fn new(start: char, end: char) -> Self {
+        assert!(start <= end, "The range ends before it starts");
+        Self { start, end }
+    }
+
+    pub fn get_start(self) -> char {
+        self.start
+    }
+
+    pub fn get_end(self) -> char {
+        self.end
+    }
+
+    pub fn contains(self, alphabet: char) -> bool {
+        self.start <= alphabet && alphabet <= self.end
+    }
+
+    /// Iterates over the characters of the range, skipping the surrogate code points.
+    pub fn chars(self) -> RangeInclusive<char> {
+        self.start..=self.end
+    }
+
+    pub(crate) fn is_single(self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl From<char> for CharRange {
+    fn from(value: char) -> Self {
+        Self::new(value, value)
+    }
+}
+
+impl From<RangeInclusive<char>> for CharRange {
+    fn from(value: RangeInclusive<char>) -> Self {
+        Self::new(*value.start(), *value.end())
+    }
+}
+
+impl From<CharRange> for RangeInclusive<char> {
+    fn from(value: CharRange) -> Self {
+        value.chars()
+    }
+}
+
+// Control and unassigned characters are escaped, as the ends of `.` or `[^a]` often are
+impl fmt::Display for CharRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let escape = |ch: char| {
+            if is_printable(ch) {
+                ch.to_string()
+            } else {
+                ch.escape_debug().to_string()
+            }
+        };
+        if self.is_single() {
+            write!(f, "{}", escape(self.start))
+        } else {
+            write!(f, "{}-{}", escape(self.start), escape(self.end))
+        }
+    }
+}
+
+impl fmt::Debug for CharRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+pub(crate) fn is_printable(alphabet: char) -> bool {
+    matches!(alphabet, '\\' | '\'' | '"') || alphabet.escape_debug().len() == 1
+}
+
+pub(crate) fn next_char(alphabet: char) -> Option<char> {
+    match alphabet {
+        '\u{d7ff}' => Some('\u{e000}'),
+        _ => char::from_u32(alphabet as u32 + 1),
+    }
+}
+
+pub(crate) fn previous_char(alphabet: char) -> Option<char> {
+    match alphabet {
+        '\u{e000}' => Some('\u{d7ff}'),
+        _ => char::from_u32((alphabet as u32).checked_sub(1)?),
+    }
+}
+
+// Splits the ranges at every boundary into sorted and disjoint atoms covering their union, so
+// that every range is a union of atoms and an atom is inside a range as soon as its start is
+pub(crate) fn split_into_atoms(ranges: impl IntoIterator<Item = CharRange>) -> Vec<CharRange> {
+    // Coverage changes at the start of every range and right after its end, `None` being the
+    // end of the Unicode range
+    let mut boundaries: BTreeMap<Option<char>, i32> = BTreeMap::new();
+    for range in ranges {
+        *boundaries.entry(Some(range.start)).or_default() += 1;
+        *boundaries.entry(next_char(range.end)).or_default() -= 1;
+    }
+    // `None` sorts first, so it is moved to the end
+    let end_count = boundaries.remove(&None);
+    let boundaries = boundaries
+        .into_iter()
+        .chain(end_count.map(|count| (None, count)))
+        .collect_vec();
+    let mut atoms = Vec::new();
+    let mut coverage = 0;
+    for ((start, count), (end, _)) in boundaries.iter().tuple_windows() {
+        coverage += count;
+        if coverage > 0 {
+            let end = end.map_or(Some(char::MAX), previous_char);
+            atoms.push(CharRange::new(start.unwrap(), end.unwrap()));
+        }
+    }
+    atoms
+}
+
+// Sorted, disjoint and non adjacent ranges covering the same characters
+pub(crate) fn normalize_ranges(ranges: impl IntoIterator<Item = CharRange>) -> Vec<CharRange> {
+    let mut normalized: Vec<CharRange> = Vec::new();
+    for range in ranges.into_iter().sorted_unstable() {
+        match normalized.last_mut() {
+            Some(last) if next_char(last.end).is_none_or(|next| next >= range.start) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => normalized.push(range),
+        }
+    }
+    normalized
+}
+
+// The characters of the whole Unicode range outside of `ranges`
+pub(crate) fn complement_ranges(ranges: impl IntoIterator<Item = CharRange>) -> Vec<CharRange> {
+    let mut complement = Vec::new();
+    let mut start = Some('\0');
+    for range in normalize_ranges(ranges) {
+        if let (Some(gap_start), Some(gap_end)) = (start, previous_char(range.start)) {
+            if gap_start <= gap_end {
+                complement.push(CharRange::new(gap_start, gap_end));
+            }
+        }
+        start = next_char(range.end);
+    }
+    if let Some(start) = start {
+        complement.push(CharRange::new(start, char::MAX));
+    }
+    complement
+}
+
+// The smallest character outside of `ranges`, which default edges read
+pub(crate) fn get_first_outside(ranges: &[CharRange]) -> Option<char> {
+    complement_ranges(ranges.iter().copied())
+        .first()
+        .map(|range| range.start)
+}
+
+// The range of sorted and disjoint `ranges` that contains `alphabet`
+pub(crate) fn find_range(ranges: &[CharRange], alphabet: char) -> Option<CharRange> {
+    let index = ranges.partition_point(|range| range.end < alphabet);
+    ranges
+        .get(index)
+        .copied()
+        .filter(|range| range.contains(alphabet))
+}
+
+/// The transitions leaving a state: sorted and disjoint character ranges, each with its target,
+/// and a default target for every character outside the alphabet of the automaton.
+///
+/// The default edge spares a table entry per character for transitions such as `.` or `[^x]`.
+/// It only reads the characters that the alphabet leaves out, so a character of the alphabet
+/// without a range of its own has no transition at all. Neighbouring ranges with the same
+/// target are merged.
+///
+/// ```
+/// use re_rs::{CharRange, RangeMap};
+///
+/// let mut transitions = RangeMap::new();
+/// transitions.insert('a'..='z', 1);
+/// transitions.insert('m', 2);
+/// transitions.set_default(Some(0));
+/// assert_eq!(transitions.get('c'), Some(&1));
+/// assert_eq!(transitions.get('m'), Some(&2));
+/// assert_eq!(transitions.get('0'), None);
+/// assert_eq!(transitions.get_default(), Some(&0));
+///
+/// transitions.insert('m', 1);
+/// assert_eq!(transitions.get_ranges(), [(CharRange::from('a'..='z'), 1)]);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RangeMap<T> {
+    ranges: Vec<(CharRange, T)>,
+    default: Option<T>,
+}
+
+impl<T> Default for RangeMap<T> {
+    fn default() -> Self {
+        Self {
+            ranges: Vec::new(),
+            default: None,
+        }
+    }
+}
+
+impl<T> RangeMap<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_ranges(&self) -> &[(CharRange, T)] {
+        &self.ranges
+    }
+
+    /// The target of the range containing `alphabet`, without falling back to the default.
+    pub fn get(&self, alphabet: char) -> Option<&T> {
+        let index = self
+            .ranges
+            .partition_point(|(range, _)| range.end < alphabet);
+        self.ranges
+            .get(index)
+            .filter(|(range, _)| range.contains(alphabet))
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_default(&self) -> Option<&T> {
+        self.default.as_ref()
+    }
+
+    pub fn set_default(&mut self, default: Option<T>) {
+        self.default = default;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.default.is_none()
+    }
+
+    // The targets of the ranges in order, then the default one
+    pub(crate) fn values(&self) -> impl Iterator<Item = &T> {
+        self.ranges
+            .iter()
+            .map(|(_, value)| value)
+            .chain(&self.default)
+    }
+}
+
+impl<T: Clone + Eq> RangeMap<T> {
+    /// Sends every character of `range` to `value`, replacing the targets it had.
+    pub fn insert(&mut self, range: impl Into<CharRange>, value: T) {
+        self.update(range.into(), |_| value.clone());
+    }
+
+    // Takes sorted and disjoint ranges
+    pub(crate) fn from_sorted(
+        ranges: impl IntoIterator<Item = (CharRange, T)>,
+        default: Option<T>,
+    ) -> Self {
+        let mut range_map = Self {
+            ranges: Vec::new(),
+            default,
+        };
+        for (range, value) in ranges {
+            range_map.push(range, value);
+        }
+        range_map
+    }
+
+    // Appends a range after the last one, merging them when they meet with the same target
+    fn push(&mut self, range: CharRange, value: T) {
+        match self.ranges.last_mut() {
+            Some((last, last_value))
+                if *last_value == value && next_char(last.end) == Some(range.start) =>
+            {
+                last.end = range.end;
+            }
+            _ => self.ranges.push((range, value)),
+        }
+    }
+
+    // Replaces the target of every character of `range` by `f` of the target it had, if any
+    pub(crate) fn update(&mut self, range: CharRange, mut f: impl FnMut(Option<&T>) -> T) {
+        let atoms = split_into_atoms(self.ranges.iter().map(|&(range, _)| range).chain([range]));
+        let ranges = atoms
+            .into_iter()
+            .filter_map(|atom| {
+                let value = self.get(atom.start);
+                if range.contains(atom.start) {
+                    Some((atom, f(value)))
+                } else {
+                    value.map(|value| (atom, value.clone()))
+                }
+            })
+            .collect_vec();
+        self.ranges.clear();
+        for (atom, value) in ranges {
+            self.push(atom, value);
+        }
+    }
+
+    // Keeps the ranges and the default for which `f` returns a target
+    pub(crate) fn filter_map<U: Clone + Eq>(
+        &self,
+        mut f: impl FnMut(&T) -> Option<U>,
+    ) -> RangeMap<U> {
+        RangeMap::from_sorted(
+            self.ranges
+                .iter()
+                .filter_map(|(range, value)| Some((*range, f(value)?)))
+                .collect_vec(),
+            self.default.as_ref().and_then(&mut f),
+        )
+    }
+
+    pub(crate) fn map<U: Clone + Eq>(&self, mut f: impl FnMut(&T) -> U) -> RangeMap<U> {
+        self.filter_map(|value| Some(f(value)))
+    }
+}
@@ -0,0 +1,315 @@
+use super::*;
+
+// Layout of a serialized DFA, as 32-bit words in the byte order given by the byte order mark:
+//
+//     magic             b"RERS"
+//     byte order mark   0xFEFF
+//     version           FORMAT_VERSION
+//     classes count     equivalence classes, class 0 being every character outside the alphabet
+//     rows count        states of the transition table, row 0 being the dead state
+//     ranges count      character ranges of the alphabet
+//     start state       premultiplied state ID with the final and trapped flags
+//     ascii classes     128 words, the class of every ASCII character
+//     class ranges      (start, end, class) triples, sorted and disjoint
+//     transition table  rows count * classes count state IDs
+//
+// The final and trapped states are the flags carried by the state IDs, exactly as in memory.
+const MAGIC: [u8; 4] = *b"RERS";
+const BYTE_ORDER_MARK: u32 = 0xFEFF;
+const FORMAT_VERSION: u32 = 1;
+const HEADER_WORDS: usize = 7;
+const ASCII_WORDS: usize = 128;
+
+/// A compiled DFA read in place from the bytes written by [`DFA::to_le_bytes`],
+/// [`DFA::to_be_bytes`] or [`DFA::to_ne_bytes`].
+///
+/// Loading validates the whole buffer but copies nothing except the 128 entry ASCII class
+/// table; the transition table and the character ranges are read from the buffer while
+/// matching, so the bytes need no particular alignment and can come straight from
+/// [`include_bytes!`].
+///
+/// ```
+/// use re_rs::{compile_regex, CustomError, SerializedDFA};
+///
+/// let dfa = compile_regex("a(b|c)*d").unwrap();
+/// let bytes = dfa.to_be_bytes();
+/// let serialized = SerializedDFA::from_bytes(&bytes).unwrap();
+/// assert!(serialized.accepts("abcbd"));
+/// assert_eq!(serialized.find("xxabdx"), Some(2..5));
+/// assert!(serialized.to_dfa().is_isomorphic(&dfa));
+///
+/// // Characters outside the alphabet follow the default edges
+/// for pattern in [".", "[^x]", "a.b"] {
+///     let dfa = compile_regex(pattern).unwrap();
+///     let bytes = dfa.to_le_bytes();
+///     assert!(SerializedDFA::from_bytes(&bytes).unwrap().to_dfa().is_isomorphic(&dfa));
+/// }
+/// let bytes = compile_regex("[^x]+").unwrap().to_ne_bytes();
+/// let serialized = SerializedDFA::from_bytes(&bytes).unwrap();
+/// assert!(serialized.accepts("yé€") && !serialized.accepts("yx"));
+/// assert_eq!(serialized.find("xx€zx"), Some(2..6));
+///
+/// let mut corrupted = dfa.to_le_bytes();
+/// let last = corrupted.len() - 1;
+/// corrupted[last] ^= 0x10;
+/// assert_eq!(
+///     SerializedDFA::from_bytes(&corrupted).err(),
+///     Some(CustomError::InvalidBinaryFormat { offset: last - 3 })
+/// );
+///
+/// // A known class of a live state sent to the dead state, with the dead state's own ID
+/// let mut corrupted = dfa.to_le_bytes();
+/// let word = |index: usize| u32::from_le_bytes(corrupted[4 * index..][..4].try_into().unwrap());
+/// let (classes_count, ranges_count) = (word(3) as usize, word(5) as usize);
+/// let table = 4 * (7 + 128 + 3 * ranges_count);
+/// let dead_state = word(table / 4);
+/// let offset = table + 4 * (classes_count + 1);
+/// corrupted[offset..offset + 4].copy_from_slice(&dead_state.to_le_bytes());
+/// assert_eq!(
+///     SerializedDFA::from_bytes(&corrupted).err(),
+///     Some(CustomError::InvalidBinaryFormat { offset })
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct SerializedDFA<'b> {
+    dfa: OptimizedDFA<'b>,
+    match_kind: MatchKind,
+}
+
+impl<'b> SerializedDFA<'b> {
+    /// Checks the header, that every state ID and class points inside the tables, that only the
+    /// characters outside the alphabet may lead to the dead state, that every state keeps the
+    /// same flags and that no trapped state can reach a final state. Errors are
+    /// reported as [`CustomError::InvalidBinaryFormat`] with the byte offset of the offending
+    /// word.
+    pub fn from_bytes(bytes: &'b [u8]) -> Result<Self, CustomError> {
+        Ok(Self {
+            dfa: read_dfa(bytes)?,
+            match_kind: MatchKind::default(),
+        })
+    }
+
+    pub fn get_match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Fails with [`CustomError::UnsupportedMatchKind`] for [`MatchKind::LeftmostFirst`].
+    pub fn set_match_kind(&mut self, match_kind: MatchKind) -> Result<(), CustomError> {
+        self.match_kind = check_match_kind(match_kind)?;
+        Ok(())
+    }
+
+    pub fn accepts(&self, text: &str) -> bool {
+        self.dfa.accepts(text)
+    }
+
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        self.dfa.find(text, self.match_kind)
+    }
+
+    pub fn find_iter<'d, 't>(&'d self, text: &'t str) -> MatchIterator<'d, 't> {
+        MatchIterator::new(
+            Searcher::Compiled(Cow::Borrowed(&self.dfa)),
+            text,
+            self.match_kind,
+        )
+    }
+
+    pub fn to_dfa(&self) -> DFA {
+        let mut dfa = DFA::from(&self.dfa);
+        dfa.set_match_kind(self.match_kind)
+            .expect("The match kind was checked when set");
+        dfa
+    }
+}
+
+pub(crate) fn write_dfa(dfa: &OptimizedDFA, is_big_endian: bool) -> Vec<u8> {
+    let class_ranges = dfa.get_class_ranges().collect_vec();
+    let words = [
+        BYTE_ORDER_MARK,
+        FORMAT_VERSION,
+        dfa.classes_count as u32,
+        (dfa.transition_table.len() / dfa.classes_count) as u32,
+        class_ranges.len() as u32,
+        dfa.start_state,
+    ]
+    .into_iter()
+    .chain(dfa.ascii_classes)
+    .chain(
+        class_ranges
+            .into_iter()
+            .flat_map(|(start, end, class)| [start, end, class]),
+    )
+    .chain((0..dfa.transition_table.len()).map(|index| dfa.transition_table.get(index)));
+    let mut bytes = MAGIC.to_vec();
+    for word in words {
+        if is_big_endian {
+            bytes.extend(word.to_be_bytes());
+        } else {
+            bytes.extend(word.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn read_dfa(bytes: &[u8]) -> Result<OptimizedDFA<'_>, CustomError> {
+    let error = |word_index: usize| CustomError::InvalidBinaryFormat {
+        offset: 4 * word_index,
+    };
+    if !bytes.len().is_multiple_of(4) || bytes.len() < 4 * (HEADER_WORDS + ASCII_WORDS) {
+        return Err(CustomError::InvalidBinaryFormat {
+            offset: bytes.len() / 4 * 4,
+        });
+    }
+    if bytes[..4] != MAGIC {
+        return Err(error(0));
+    }
+    let byte_order_mark: [u8; 4] = bytes[4..8].try_into().unwrap();
+    let is_big_endian = if u32::from_le_bytes(byte_order_mark) == BYTE_ORDER_MARK {
+        false
+    } else if u32::from_be_bytes(byte_order_mark) == BYTE_ORDER_MARK {
+        true
+    } else {
+        return Err(error(1));
+    };
+    let words = Table::Bytes {
+        bytes,
+        is_big_endian,
+    };
+    if words.get(2) != FORMAT_VERSION {
+        return Err(error(2));
+    }
+    let classes_count = words.get(3) as usize;
+    let rows_count = words.get(4) as usize;
+    let ranges_count = words.get(5) as usize;
+    let start_state = words.get(6);
+    if classes_count == 0 {
+        return Err(error(3));
+    }
+    if rows_count == 0
+        || rows_count
+            .checked_mul(classes_count)
+            .is_none_or(|size| size > STATE_MASK as usize)
+    {
+        return Err(error(4));
+    }
+    let ranges_offset = HEADER_WORDS + ASCII_WORDS;
+    let table_offset = ranges_count
+        .checked_mul(3)
+        .and_then(|ranges_words| ranges_words.checked_add(ranges_offset))
+        .ok_or(error(5))?;
+    if words.len().checked_sub(table_offset) != Some(rows_count * classes_count) {
+        return Err(error(5));
+    }
+
+    // Character ranges: valid characters, sorted, disjoint and with a known class
+    let mut ascii_classes = [UNKNOWN_CLASS; 128];
+    let mut expected_ascii_classes = [UNKNOWN_CLASS; 128];
+    for (index, ascii_class) in ascii_classes.iter_mut().enumerate() {
+        *ascii_class = words.get(HEADER_WORDS + index);
+        if *ascii_class as usize >= classes_count {
+            return Err(error(HEADER_WORDS + index));
+        }
+    }
+    let mut previous_end: Option<u32> = None;
+    for index in 0..ranges_count {
+        let word_index = ranges_offset + 3 * index;
+        let (start, end, class) = (
+            words.get(word_index),
+            words.get(word_index + 1),
+            words.get(word_index + 2),
+        );
+        if char::from_u32(start).is_none() || previous_end.is_some_and(|previous| start <= previous)
+        {
+            return Err(error(word_index));
+        }
+        if char::from_u32(end).is_none() || end < start {
+            return Err(error(word_index + 1));
+        }
+        if class == UNKNOWN_CLASS || class as usize >= classes_count {
+            return Err(error(word_index + 2));
+        }
+        for alphabet in start..=end.min(127) {
+            expected_ascii_classes[alphabet as usize] = class;
+        }
+        previous_end = Some(end);
+    }
+    if let Some(index) =
+        (0..128).find(|&index| ascii_classes[index] != expected_ascii_classes[index])
+    {
+        return Err(error(HEADER_WORDS + index));
+    }
+
+    // Transition table: every state ID is a row with consistent flags and the dead state only
+    // leads to itself. The unknown class follows the default edges, so it may lead to any row,
+    // while the known classes of the other rows never lead to the dead state.
+    let mut row_flags: Vec<Option<u32>> = vec![None; rows_count];
+    row_flags[0] = Some(DEAD_STATE);
+    let mut reversed_edges: Vec<Vec<usize>> = vec![Vec::new(); rows_count];
+    let mut check_state_id = |state_id: u32, word_index: usize| {
+        let offset = (state_id & STATE_MASK) as usize;
+        let row = offset / classes_count;
+        if !offset.is_multiple_of(classes_count) || row >= rows_count {
+            return Err(error(word_index));
+        }
+        let flags = state_id & !STATE_MASK;
+        if *row_flags[row].get_or_insert(flags) != flags {
+            return Err(error(word_index));
+        }
+        Ok(row)
+    };
+    if check_state_id(start_state, 6)? == 0 {
+        return Err(error(6));
+    }
+    for row in 0..rows_count {
+        for class in 0..classes_count {
+            let word_index = table_offset + row * classes_count + class;
+            let state_id = words.get(word_index);
+            if row == 0 && state_id != DEAD_STATE {
+                return Err(error(word_index));
+            }
+            let to = check_state_id(state_id, word_index)?;
+            // Only the unknown class may lack a state to go to, as the table is total
+            if to == 0 && row != 0 && class != UNKNOWN_CLASS as usize {
+                return Err(error(word_index));
+            }
+            reversed_edges[to].push(row);
+        }
+    }
+
+    // No trapped state may reach a final state, as matching stops at the first trapped state
+    let mut reaches_final = vec![false; rows_count];
+    let mut stack = (0..rows_count)
+        .filter(|&row| row_flags[row].is_some_and(|flags| flags & FINAL_FLAG != 0))
+        .collect_vec();
+    for &row in &stack {
+        reaches_final[row] = true;
+    }
+    while let Some(row) = stack.pop() {
+        for &from in &reversed_edges[row] {
+            if !reaches_final[from] {
+                reaches_final[from] = true;
+                stack.push(from);
+            }
+        }
+    }
+    for row in 0..rows_count {
+        if reaches_final[row] && row_flags[row].is_some_and(|flags| flags & TRAPPED_FLAG != 0) {
+            return Err(error(table_offset + row * classes_count));
+        }
+    }
+
+    Ok(OptimizedDFA {
+        class_ranges: Table::Bytes {
+            bytes: &bytes[4 * ranges_offset..4 * table_offset],
+            is_big_endian,
+        },
+        ascii_classes,
+        classes_count,
+        transition_table: Table::Bytes {
+            bytes: &bytes[4 * table_offset..],
+            is_big_endian,
+        },
+        start_state,
+    })
+}
@@ -0,0 +1,358 @@
+use super::*;
+use regex::{compile_operator, Ast};
+use std::ops::Index;
+use std::sync::Arc;
+
+// A Pike VM program for the capture groups: the Thompson construction of `Compiler`, but with
+// the epsilon transitions ordered by priority and tagged with the slots they save the
+// position to
+#[derive(Debug, Clone)]
+enum Instruction {
+    // Any character of the sorted and disjoint ranges
+    Class(Vec<CharRange>),
+    // Epsilon transitions to the targets, in decreasing priority
+    Split(Vec<usize>),
+    Save(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CaptureProgram {
+    instructions: Vec<Instruction>,
+    group_names: Arc<[Option<String>]>,
+    pattern_alphabets: Vec<CharRange>,
+}
+
+impl CaptureProgram {
+    pub(crate) fn new(ast: &Ast, group_names: Vec<Option<String>>) -> Self {
+        let mut program = Self {
+            instructions: Vec::new(),
+            group_names: group_names.into(),
+            pattern_alphabets: ast.get_alphabets(),
+        };
+        program.compile(ast);
+        program.instructions.push(Instruction::Match);
+        program
+    }
+
+    pub(crate) fn get_group_names(&self) -> &[Option<String>] {
+        &self.group_names
+    }
+
+    fn push(&mut self, instruction: Instruction) -> usize {
+        self.instructions.push(instruction);
+        self.instructions.len() - 1
+    }
+
+    // Points a placeholder `Split` at its targets
+    fn patch(&mut self, index: usize, targets: Vec<usize>) {
+        self.instructions[index] = Instruction::Split(targets);
+    }
+
+    // The quantifiers are greedy, so the split into the repeated part comes first
+    fn compile(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Empty => {}
+            Ast::Literal(ch) => {
+                self.push(Instruction::Class(vec![CharRange::from(*ch)]));
+            }
+            Ast::Class(ranges) => {
+                self.push(Instruction::Class(ranges.clone()));
+            }
+            Ast::Concat(items) => {
+                for item in items {
+                    self.compile(item);
+                }
+            }
+            Ast::Alternation(branches) => {
+                let split = self.push(Instruction::Split(Vec::new()));
+                let mut targets = Vec::new();
+                let mut jumps = Vec::new();
+                for branch in branches {
+                    targets.push(self.instructions.len());
+                    self.compile(branch);
+                    jumps.push(self.push(Instruction::Split(Vec::new())));
+                }
+                self.patch(split, targets);
+                let end = self.instructions.len();
+                for jump in jumps {
+                    self.patch(jump, vec![end]);
+                }
+            }
+            // Compiled as `(x+)?`, so that an empty iteration still saves the groups inside
+            Ast::Star(inner) => {
+                let split = self.push(Instruction::Split(Vec::new()));
+                self.compile(inner);
+                let repeat = self.push(Instruction::Split(Vec::new()));
+                self.patch(repeat, vec![split + 1, repeat + 1]);
+                self.patch(split, vec![split + 1, repeat + 1]);
+            }
+            Ast::Plus(inner) => {
+                let start = self.instructions.len();
+                self.compile(inner);
+                let split = self.push(Instruction::Split(Vec::new()));
+                self.patch(split, vec![start, split + 1]);
+            }
+            Ast::Optional(inner) => {
+                let split = self.push(Instruction::Split(Vec::new()));
+                self.compile(inner);
+                self.patch(split, vec![split + 1, self.instructions.len()]);
+            }
+            Ast::Group(index, inner) => {
+                self.push(Instruction::Save(2 * index));
+                self.compile(inner);
+                self.push(Instruction::Save(2 * index + 1));
+            }
+            Ast::Intersection(..) | Ast::Difference(..) | Ast::Complement(..) => {
+                let dfa = compile_operator(ast, &self.pattern_alphabets);
+                self.compile_dfa(&dfa);
+            }
+        }
+    }
+
+    // Lays out every state of `dfa` that can still reach a final state as a `Split` over its
+    // transitions, each a `Class` and a jump to the target, and over the exit when the state is
+    // final. The DFA is deterministic so the order of the transitions does not matter, and
+    // the exit comes last to keep the match greedy.
+    fn compile_dfa(&mut self, dfa: &DFA) {
+        // The start state comes first and stays even when trapped, as a `Split` to nowhere
+        let states = [*dfa.get_start_state()]
+            .into_iter()
+            .chain(
+                dfa.get_states()
+                    .iter()
+                    .filter(|&state| {
+                        state != dfa.get_start_state() && !dfa.get_trapped_states().contains(state)
+                    })
+                    .sorted_unstable()
+                    .copied(),
+            )
+            .collect_vec();
+        let transitions = |state: State| {
+            dfa.get_explicit_transitions(state)
+                .into_iter()
+                .filter(|(_, to)| !dfa.get_trapped_states().contains(to))
+                .collect_vec()
+        };
+        let mut offsets = HashMap::new();
+        let mut offset = self.instructions.len();
+        for &state in &states {
+            offsets.insert(state, offset);
+            offset += 1 + 2 * transitions(state).len();
+        }
+        let exit = offset;
+        for &state in &states {
+            let split = self.push(Instruction::Split(Vec::new()));
+            let mut targets = Vec::new();
+            for (alphabet, to) in transitions(state) {
+                targets.push(self.push(Instruction::Class(vec![alphabet])));
+                self.push(Instruction::Split(vec![offsets[&to]]));
+            }
+            if dfa.get_final_states().contains(&state) {
+                targets.push(exit);
+            }
+            self.patch(split, targets);
+        }
+    }
+
+    // Runs the program anchored on `range`, a match found by the DFA, and returns the slots of
+    // the highest priority thread that matches exactly that range. Threads are kept in priority
+    // order and an instruction only keeps its first thread, so every instruction is visited
+    // once per character.
+    fn captures<'t>(&self, text: &'t str, range: Range<usize>) -> Captures<'t> {
+        let slots_count = 2 * self.group_names.len();
+        let mut marks = vec![usize::MAX; self.instructions.len()];
+        let mut threads = Vec::new();
+        let mut initial_slots = vec![None; slots_count];
+        initial_slots[0] = Some(range.start);
+        self.add_thread(0, range.start, 0, initial_slots, &mut marks, &mut threads);
+        let positions = text[range.clone()]
+            .char_indices()
+            .map(|(offset, ch)| (range.start + offset, ch));
+        for (step, (position, ch)) in positions.enumerate() {
+            let next_position = position + ch.len_utf8();
+            let mut next_threads = Vec::new();
+            for (index, slots) in threads {
+                if matches!(&self.instructions[index], Instruction::Class(ranges) if find_range(ranges, ch).is_some())
+                {
+                    self.add_thread(
+                        index + 1,
+                        next_position,
+                        step + 1,
+                        slots,
+                        &mut marks,
+                        &mut next_threads,
+                    );
+                }
+            }
+            threads = next_threads;
+        }
+        let mut slots = threads
+            .into_iter()
+            .find(|&(index, _)| matches!(self.instructions[index], Instruction::Match))
+            .map(|(_, slots)| slots)
+            .expect("The DFA match is accepted by the capture program");
+        slots[1] = Some(range.end);
+        Captures {
+            text,
+            slots,
+            group_names: Arc::clone(&self.group_names),
+        }
+    }
+
+    // Finds the leftmost-first match, running the program from every position at once. A thread
+    // started at a later position comes after all the others, so the threads stay in priority
+    // order, and a thread that matches discards the ones after it: the threads before it may
+    // still find a match of higher priority.
+    pub(crate) fn find_first(&self, text: &str) -> Option<Range<usize>> {
+        let mut marks = vec![usize::MAX; self.instructions.len()];
+        let mut threads = Vec::new();
+        let mut found = None;
+        let positions = text
+            .char_indices()
+            .map(|(position, ch)| (position, Some(ch)))
+            .chain([(text.len(), None)]);
+        for (step, (position, ch)) in positions.enumerate() {
+            if found.is_none() {
+                let mut slots = vec![None; 2 * self.group_names.len()];
+                slots[0] = Some(position);
+                self.add_thread(0, position, step, slots, &mut marks, &mut threads);
+            }
+            let mut next_threads = Vec::new();
+            for (index, slots) in threads {
+                match &self.instructions[index] {
+                    Instruction::Match => {
+                        found = slots[0].map(|start| start..position);
+                        break;
+                    }
+                    Instruction::Class(ranges) => {
+                        if let Some(ch) = ch.filter(|&ch| find_range(ranges, ch).is_some()) {
+                            self.add_thread(
+                                index + 1,
+                                position + ch.len_utf8(),
+                                step + 1,
+                                slots,
+                                &mut marks,
+                                &mut next_threads,
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            threads = next_threads;
+            if threads.is_empty() && found.is_some() {
+                break;
+            }
+        }
+        found
+    }
+
+    // Follows the epsilon transitions from `index` in priority order, saving the positions on
+    // the way, and adds the threads that stop on a `Class` or `Match`
+    fn add_thread(
+        &self,
+        index: usize,
+        position: usize,
+        step: usize,
+        mut slots: Vec<Option<usize>>,
+        marks: &mut [usize],
+        threads: &mut Vec<(usize, Vec<Option<usize>>)>,
+    ) {
+        if marks[index] == step {
+            return;
+        }
+        marks[index] = step;
+        match &self.instructions[index] {
+            Instruction::Class(_) | Instruction::Match => threads.push((index, slots)),
+            Instruction::Split(targets) => {
+                for &target in targets {
+                    self.add_thread(target, position, step, slots.clone(), marks, threads);
+                }
+            }
+            Instruction::Save(slot) => {
+                slots[*slot] = Some(position);
+                self.add_thread(index + 1, position, step, slots, marks, threads);
+            }
+        }
+    }
+}
+
+/// The spans of the capture groups of a single match, created by [`Regex::captures`].
+///
+/// Groups are numbered from 1 in the order of their opening parenthesis, group 0 being the
+/// whole match. Indexing with a number or a name returns the text of the group and panics if
+/// the group took no part in the match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures<'t> {
+    text: &'t str,
+    // The start and end of every group
+    slots: Vec<Option<usize>>,
+    group_names: Arc<[Option<String>]>,
+}
+
+impl<'t> Captures<'t> {
+    pub fn get(&self, index: usize) -> Option<Match<'t>> {
+        let start = (*self.slots.get(2 * index)?)?;
+        let end = self.slots[2 * index + 1]?;
+        Some(Match::new(self.text, start..end))
+    }
+
+    pub fn name(&self, name: &str) -> Option<Match<'t>> {
+        let index = self
+            .group_names
+            .iter()
+            .position(|group_name| group_name.as_deref() == Some(name))?;
+        self.get(index)
+    }
+
+    /// The number of groups, counting the whole match.
+    pub fn len(&self) -> usize {
+        self.group_names.len()
+    }
+
+    /// Always false, as the whole match is a group.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'t>>> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+}
+
+impl Index<usize> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &str {
+        self.get(index)
+            .unwrap_or_else(|| panic!("No match for group {index}"))
+            .as_str()
+    }
+}
+
+impl Index<&str> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        self.name(name)
+            .unwrap_or_else(|| panic!("No match for group '{name}'"))
+            .as_str()
+    }
+}
+
+/// Iterator over the non-overlapping matches of a [`Regex`] with their capture groups, created
+/// by [`Regex::captures_iter`].
+pub struct CaptureMatches<'r, 't> {
+    pub(crate) matches: Matches<'r, 't>,
+    pub(crate) program: &'r CaptureProgram,
+}
+
+impl<'t> Iterator for CaptureMatches<'_, 't> {
+    type Item = Captures<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = self.matches.next()?;
+        Some(self.program.captures(self.matches.text, found.range()))
+    }
+}
@@ -5,5 +5,4 @@ pub const AUTO_OPTIMIZE: bool = false;
 #[cfg(not(debug_assertions))]
 pub const AUTO_OPTIMIZE: bool = true;
 
-pub const STATE_ARRAY_SIZE: usize = 100;
 pub const DEFAULT_STATE_SYMBOL: char = 'q';
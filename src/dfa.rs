@@ -1,167 +1,808 @@
 use super::*;
 
+// State IDs of the compiled matcher are premultiplied row offsets into the transition table
+// with the final and trapped flags packed into the two highest bits
+pub(crate) const FINAL_FLAG: u32 = 1 << 31;
+pub(crate) const TRAPPED_FLAG: u32 = 1 << 30;
+pub(crate) const STATE_MASK: u32 = !(FINAL_FLAG | TRAPPED_FLAG);
+// Class of every character outside the alphabet, which follows the default edge of the state
+// or leads to the dead state in row 0 without one
+pub(crate) const UNKNOWN_CLASS: u32 = 0;
+pub(crate) const DEAD_STATE: u32 = TRAPPED_FLAG;
+
+// Storage for the words of the compiled matcher: built in memory, or read in place from a
+// serialized buffer of either byte order
+#[derive(Debug, Clone)]
+pub(crate) enum Table<'b> {
+    Owned(Vec<u32>),
+    Bytes {
+        bytes: &'b [u8],
+        is_big_endian: bool,
+    },
+}
+
+impl Table<'_> {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Owned(words) => words.len(),
+            Self::Bytes { bytes, .. } => bytes.len() / 4,
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> u32 {
+        match self {
+            Self::Owned(words) => words[index],
+            Self::Bytes {
+                bytes,
+                is_big_endian,
+            } => {
+                let word = bytes[4 * index..4 * index + 4].try_into().unwrap();
+                if *is_big_endian {
+                    u32::from_be_bytes(word)
+                } else {
+                    u32::from_le_bytes(word)
+                }
+            }
+        }
+    }
+}
+
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
-struct OptimizedDFA {
-    states: HashSet<usize>,
-    alphabets: HashSet<char>,
-    transition_table: HashMap<(usize, char), usize>,
-    start_state: usize,
-    current_state: usize,
-    final_states: HashSet<usize>,
-    trapped_states: HashSet<usize>,
+pub(crate) struct OptimizedDFA<'b> {
+    // Sorted, disjoint and non adjacent character ranges with their equivalence class, stored
+    // flat as (start, end, class) triples
+    pub class_ranges: Table<'b>,
+    pub ascii_classes: [u32; 128],
+    pub classes_count: usize,
+    pub transition_table: Table<'b>,
+    pub start_state: u32,
 }
 
-impl OptimizedDFA {
-    pub fn accepts(&mut self, text: &str) -> Result<bool, CustomError> {
-        self.current_state = self.start_state;
+impl OptimizedDFA<'_> {
+    pub fn accepts(&self, text: &str) -> bool {
+        let mut current_state = self.start_state;
         for ch in text.chars() {
-            self.update_to_next_state(ch)?;
+            current_state = self.get_next_state(current_state, ch);
         }
-        Ok(self.final_states.contains(&self.current_state))
+        current_state & FINAL_FLAG != 0
     }
 
-    pub fn update_to_next_state(&mut self, alphabet: char) -> Result<(), CustomError> {
-        // let curr_state = self.current_state;
-        self.current_state = *self
-            .transition_table
-            .get(&(self.current_state, alphabet))
-            .ok_or(CustomError::InvalidAlphabet)?;
-        // println!(
-        //     "State changed from {} to {}",
-        //     curr_state.get_index(),
-        //     self.current_state.get_index()
-        // );
-        Ok(())
+    // The (start, end, class) triples of the character ranges
+    pub fn get_class_ranges(&self) -> impl Iterator<Item = (u32, u32, u32)> + '_ {
+        (0..self.class_ranges.len() / 3).map(|index| {
+            (
+                self.class_ranges.get(3 * index),
+                self.class_ranges.get(3 * index + 1),
+                self.class_ranges.get(3 * index + 2),
+            )
+        })
+    }
+
+    #[inline]
+    fn get_class(&self, alphabet: char) -> usize {
+        if alphabet.is_ascii() {
+            return self.ascii_classes[alphabet as usize] as usize;
+        }
+        let alphabet = alphabet as u32;
+        let (mut low, mut high) = (0, self.class_ranges.len() / 3);
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.class_ranges.get(3 * middle + 1) < alphabet {
+                low = middle + 1;
+            } else if self.class_ranges.get(3 * middle) > alphabet {
+                high = middle;
+            } else {
+                return self.class_ranges.get(3 * middle + 2) as usize;
+            }
+        }
+        UNKNOWN_CLASS as usize
+    }
+
+    #[inline]
+    fn get_next_state(&self, state: u32, alphabet: char) -> u32 {
+        self.transition_table
+            .get((state & STATE_MASK) as usize + self.get_class(alphabet))
+    }
+
+    pub fn find(&self, text: &str, match_kind: MatchKind) -> Option<Range<usize>> {
+        find_leftmost(&mut &*self, text, match_kind, &mut Marks::default())
+    }
+}
+
+impl Search for &OptimizedDFA<'_> {
+    type State = u32;
+
+    fn get_start(&mut self) -> Option<u32> {
+        (self.start_state & TRAPPED_FLAG == 0).then_some(self.start_state)
+    }
+
+    fn is_final(&self, state: &u32) -> bool {
+        state & FINAL_FLAG != 0
+    }
+
+    // Unknown characters without a default edge lead to the dead state, which is trapped as well
+    fn step(&mut self, threads: &mut Vec<(u32, usize)>, alphabet: char, marks: &mut Marks) {
+        threads.retain_mut(|(state, _)| {
+            *state = self.get_next_state(*state, alphabet);
+            *state & TRAPPED_FLAG == 0
+                && marks.insert((*state & STATE_MASK) as usize / self.classes_count)
+        });
     }
+}
 
-    pub fn find(&mut self, text: &str) -> Result<Option<Range<usize>>, CustomError> {
-        self.current_state = self.start_state;
-        let mut start = 0;
-        let mut end = 0;
-        let mut match_found = self.final_states.contains(&self.current_state);
+impl From<&DFA> for OptimizedDFA<'_> {
+    fn from(value: &DFA) -> Self {
+        let states = value.states.iter().copied().sorted_unstable().collect_vec();
 
-        for (i, ch) in text.char_indices() {
-            if self.trapped_states.contains(&self.current_state) {
-                self.current_state = self.start_state;
-                start = i;
-                end = i;
-                match_found = false;
+        // Characters with the same target in every state share an equivalence class, the
+        // characters outside the alphabet first
+        let signature = |alphabet: Option<char>| {
+            states
+                .iter()
+                .map(|&state| match alphabet {
+                    Some(alphabet) => value.get_next_state(state, alphabet),
+                    None => value.transition_table[&state].get_default().copied(),
+                })
+                .collect_vec()
+        };
+        let mut signatures: HashMap<Vec<Option<State>>, u32> =
+            HashMap::from([(signature(None), UNKNOWN_CLASS)]);
+        let alphabets = value.get_atoms();
+        let alphabet_classes = alphabets
+            .iter()
+            .map(|alphabet| {
+                let next_class = signatures.len() as u32;
+                *signatures
+                    .entry(signature(Some(alphabet.get_start())))
+                    .or_insert(next_class)
+            })
+            .collect_vec();
+        let classes_count = signatures.len();
+
+        let mut class_ranges: Vec<(char, char, u32)> = Vec::new();
+        for (&alphabet, &class) in alphabets.iter().zip(&alphabet_classes) {
+            match class_ranges.last_mut() {
+                _ if class == UNKNOWN_CLASS => {}
+                Some((_, end, last_class))
+                    if *last_class == class && next_char(*end) == Some(alphabet.get_start()) =>
+                {
+                    *end = alphabet.get_end();
+                }
+                _ => class_ranges.push((alphabet.get_start(), alphabet.get_end(), class)),
             }
-            self.update_to_next_state(ch)?;
-            if self.final_states.contains(&self.current_state) {
-                match_found = true;
+        }
+        let mut ascii_classes = [UNKNOWN_CLASS; 128];
+        for (&alphabet, &class) in alphabets.iter().zip(&alphabet_classes) {
+            for ch in alphabet.chars().take_while(char::is_ascii) {
+                ascii_classes[ch as usize] = class;
             }
-            if match_found && !self.final_states.contains(&self.current_state) {
-                break;
+        }
+
+        // Row 0 is the dead state, the states of the DFA follow in sorted order
+        assert!(
+            (states.len() + 1) * classes_count <= STATE_MASK as usize,
+            "DFA is too large for the dense transition table"
+        );
+        let rows: HashMap<State, usize> = states
+            .iter()
+            .enumerate()
+            .map(|(index, &state)| (state, index + 1))
+            .collect();
+        let get_state_id = |state: State| {
+            let mut state_id = (rows[&state] * classes_count) as u32;
+            if value.final_states.contains(&state) {
+                state_id |= FINAL_FLAG;
             }
-            if !self.trapped_states.contains(&self.current_state) {
-                end += ch.len_utf8();
+            if value.trapped_states.contains(&state) {
+                state_id |= TRAPPED_FLAG;
+            }
+            state_id
+        };
+        let mut transition_table = vec![DEAD_STATE; (states.len() + 1) * classes_count];
+        for (signature, &class) in &signatures {
+            for (&state, &to) in states.iter().zip(signature) {
+                if let Some(to) = to {
+                    transition_table[rows[&state] * classes_count + class as usize] =
+                        get_state_id(to);
+                }
             }
         }
-        if match_found {
-            // println!("Matched {:?}", start..end);
-            return Ok(Some(start..end));
-        }
-        Ok(None)
-    }
-}
 
-impl From<&DFA> for OptimizedDFA {
-    fn from(value: &DFA) -> Self {
         OptimizedDFA {
-            states: value.states.iter().copied().map_into().collect(),
-            alphabets: value.alphabets.clone(),
-            transition_table: value
-                .transition_table
-                .iter()
-                .map(|(&(from, alphabet), &to)| ((from.into(), alphabet), to.into()))
-                .collect(),
-            start_state: value.start_state.into(),
-            current_state: value.start_state.into(),
-            final_states: value.final_states.iter().copied().map_into().collect(),
-            trapped_states: value.trapped_states.iter().copied().map_into().collect(),
+            class_ranges: Table::Owned(
+                class_ranges
+                    .into_iter()
+                    .flat_map(|(start, end, class)| [start as u32, end as u32, class])
+                    .collect(),
+            ),
+            ascii_classes,
+            classes_count,
+            transition_table: Table::Owned(transition_table),
+            start_state: get_state_id(value.start_state),
         }
     }
 }
 
-impl From<DFA> for OptimizedDFA {
+impl From<DFA> for OptimizedDFA<'_> {
     fn from(value: DFA) -> Self {
         (&value).into()
     }
 }
 
-impl From<&OptimizedDFA> for DFA {
-    fn from(value: &OptimizedDFA) -> Self {
-        DFA::new(
-            value.states.iter().copied().map_into().collect(),
-            value.alphabets.clone(),
-            value
-                .transition_table
-                .iter()
-                .map(|(&(from, alphabet), &to)| ((from.into(), alphabet), to.into()))
+impl From<&OptimizedDFA<'_>> for DFA {
+    fn from(value: &OptimizedDFA<'_>) -> Self {
+        let get_state =
+            |state_id: u32| State::new((state_id & STATE_MASK) as usize / value.classes_count - 1);
+        let rows_count = value.transition_table.len() / value.classes_count;
+        let states = (1..rows_count)
+            .map(|row| get_state((row * value.classes_count) as u32))
+            .collect();
+        let alphabets = value
+            .get_class_ranges()
+            .map(|(start, end, class)| {
+                let range =
+                    CharRange::new(char::from_u32(start).unwrap(), char::from_u32(end).unwrap());
+                (range, class)
+            })
+            .collect_vec();
+        let mut transition_table = HashMap::new();
+        let mut final_states = HashSet::new();
+        for row in 1..rows_count {
+            let get_next_state_id = |class: u32| {
+                value
+                    .transition_table
+                    .get(row * value.classes_count + class as usize)
+            };
+            // The dead state in the unknown class is the lack of a default edge
+            let transitions = RangeMap::from_sorted(
+                alphabets
+                    .iter()
+                    .map(|&(alphabet, class)| (alphabet, get_next_state_id(class))),
+                Some(get_next_state_id(UNKNOWN_CLASS)).filter(|&to| to != DEAD_STATE),
+            );
+            for &to in transitions.values() {
+                if to & FINAL_FLAG != 0 {
+                    final_states.insert(get_state(to));
+                }
+            }
+            transition_table.insert(
+                get_state((row * value.classes_count) as u32),
+                transitions.map(|&to| get_state(to)),
+            );
+        }
+        if value.start_state & FINAL_FLAG != 0 {
+            final_states.insert(get_state(value.start_state));
+        }
+        DFA::from_range_maps(
+            states,
+            alphabets
+                .into_iter()
+                .map(|(alphabet, _)| alphabet)
                 .collect(),
-            value.start_state.into(),
-            value.final_states.iter().copied().map_into().collect(),
+            transition_table,
+            get_state(value.start_state),
+            final_states,
         )
         .unwrap()
     }
 }
 
-impl From<OptimizedDFA> for DFA {
-    fn from(value: OptimizedDFA) -> Self {
+impl From<OptimizedDFA<'_>> for DFA {
+    fn from(value: OptimizedDFA<'_>) -> Self {
         (&value).into()
     }
 }
 
-impl fmt::Display for OptimizedDFA {
+impl fmt::Display for OptimizedDFA<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", DFA::from(self))
     }
 }
 
-pub struct MatchIterator<'a> {
-    dfa: OptimizedDFA,
-    text: &'a str,
+/// Selects which match is reported when several substrings starting at or after the search
+/// position are accepted.
+///
+/// Every mode picks the leftmost starting position first; they differ in where the match
+/// ends. A DFA keeps no record of the order of the alternatives, so only [`Regex`] supports
+/// [`MatchKind::LeftmostFirst`] and the other engines refuse it with
+/// [`CustomError::UnsupportedMatchKind`]. The examples use the automaton for `a|a(a|b)*a` from
+/// the binary's `test2`:
+///
+/// ```
+/// use re_rs::dfa::MatchKind;
+/// use re_rs::{CustomError, Regex, State, DFA};
+/// use std::collections::{HashMap, HashSet};
+///
+/// let [q0, q1, q2, q3, q4] = [0, 1, 2, 3, 4].map(|index| State::new(index));
+/// let mut dfa = DFA::new(
+///     HashSet::from([q0, q1, q2, q3, q4]),
+///     HashSet::from(['a', 'b']),
+///     HashMap::from([
+///         ((q0, 'a'), q1),
+///         ((q0, 'b'), q4),
+///         ((q1, 'a'), q2),
+///         ((q1, 'b'), q3),
+///         ((q2, 'a'), q2),
+///         ((q2, 'b'), q3),
+///         ((q3, 'a'), q2),
+///         ((q3, 'b'), q3),
+///         ((q4, 'a'), q4),
+///         ((q4, 'b'), q4),
+///     ]),
+///     q0,
+///     HashSet::from([q1, q2]),
+/// )
+/// .unwrap();
+/// let text = "abaab";
+/// let matches = |dfa: &DFA| {
+///     dfa.find_all(text)
+///         .unwrap()
+///         .map(|(_, slice)| slice)
+///         .collect::<Vec<_>>()
+/// };
+///
+/// assert_eq!(dfa.get_match_kind(), MatchKind::LeftmostLongest);
+/// assert_eq!(matches(&dfa), ["abaa"]);
+///
+/// dfa.set_match_kind(MatchKind::Shortest).unwrap();
+/// assert_eq!(matches(&dfa), ["a", "a", "a"]);
+///
+/// // The first alternative `a` wins over the longer `a(a|b)*a`
+/// let mut regex = Regex::new("a|a(a|b)*a").unwrap();
+/// regex.set_match_kind(MatchKind::LeftmostFirst);
+/// let found: Vec<&str> = regex.find_iter(text).map(|found| found.as_str()).collect();
+/// assert_eq!(found, ["a", "a", "a"]);
+/// let mut regex = Regex::new("a(a|b)*a|a").unwrap();
+/// regex.set_match_kind(MatchKind::LeftmostFirst);
+/// assert_eq!(regex.find("abaab").unwrap().as_str(), "abaa");
+/// assert_eq!(
+///     dfa.set_match_kind(MatchKind::LeftmostFirst),
+///     Err(CustomError::UnsupportedMatchKind)
+/// );
+/// ```
+///
+/// The union with `ab*a` from `test3` accepts the same language as `test2`, shown here on the
+/// text `test3` searches:
+///
+/// ```
+/// # use re_rs::dfa::MatchKind;
+/// # use re_rs::{State, DFA};
+/// # use std::collections::{HashMap, HashSet};
+/// # let [q0, q1, q2, q3, q4] = [0, 1, 2, 3, 4].map(|index| State::new(index));
+/// # let dfa2 = DFA::new(
+/// #     HashSet::from([q0, q1, q2, q3, q4]),
+/// #     HashSet::from(['a', 'b']),
+/// #     HashMap::from([
+/// #         ((q0, 'a'), q1),
+/// #         ((q0, 'b'), q4),
+/// #         ((q1, 'a'), q2),
+/// #         ((q1, 'b'), q3),
+/// #         ((q2, 'a'), q2),
+/// #         ((q2, 'b'), q3),
+/// #         ((q3, 'a'), q2),
+/// #         ((q3, 'b'), q3),
+/// #         ((q4, 'a'), q4),
+/// #         ((q4, 'b'), q4),
+/// #     ]),
+/// #     q0,
+/// #     HashSet::from([q1, q2]),
+/// # )
+/// # .unwrap();
+/// let dfa1 = DFA::new(
+///     HashSet::from([q0, q1, q2, q3]),
+///     HashSet::from(['a', 'b']),
+///     HashMap::from([
+///         ((q0, 'a'), q1),
+///         ((q0, 'b'), q3),
+///         ((q1, 'a'), q2),
+///         ((q1, 'b'), q1),
+///         ((q2, 'a'), q3),
+///         ((q2, 'b'), q3),
+///         ((q3, 'a'), q3),
+///         ((q3, 'b'), q3),
+///     ]),
+///     q0,
+///     HashSet::from([q2]),
+/// )
+/// .unwrap();
+/// let mut dfa_union = dfa1.union(&dfa2).unwrap();
+/// let text = "bbabbabababbbbabbbababbababbbaabaaaaba";
+/// let matches = |dfa: &DFA| {
+///     dfa.find_all(text)
+///         .unwrap()
+///         .map(|(range, _)| range)
+///         .collect::<Vec<_>>()
+/// };
+///
+/// assert_eq!(matches(&dfa_union), [2..38]);
+///
+/// dfa_union.set_match_kind(MatchKind::Shortest).unwrap();
+/// assert_eq!(matches(&dfa_union), [2..3, 5..6, 7..8, 9..10, 14..15, 18..19, 20..21,
+///     23..24, 25..26, 29..30, 30..31, 32..33, 33..34, 34..35, 35..36, 37..38]);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchKind {
+    /// POSIX semantics: the longest match starting at the leftmost position.
+    #[default]
+    LeftmostLongest,
+    /// The shortest match starting at the leftmost position, which is empty whenever the
+    /// start state is final.
+    Shortest,
+    /// The match a backtracking engine reports at the leftmost position: the earlier
+    /// alternative first and the quantifiers greedy. Only [`Regex`] supports it.
+    LeftmostFirst,
+}
+
+// Refuses the match kinds that need the order of the alternatives, which a DFA does not keep
+pub(crate) fn check_match_kind(match_kind: MatchKind) -> Result<MatchKind, CustomError> {
+    match match_kind {
+        MatchKind::LeftmostFirst => Err(CustomError::UnsupportedMatchKind),
+        _ => Ok(match_kind),
+    }
+}
+
+// The states reached in the current step of a search, as the step a state was last reached
+// in, so that a `MatchIterator` does not pay for every state on each of its searches
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Marks {
+    steps: Vec<usize>,
+    step: usize,
+}
+
+impl Marks {
+    pub fn next_step(&mut self) {
+        self.step += 1;
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.steps.get(index) == Some(&self.step)
+    }
+
+    // Marks `index` as reached in the current step, returning whether it was not yet
+    pub fn insert(&mut self, index: usize) -> bool {
+        if index >= self.steps.len() {
+            self.steps.resize(index + 1, 0);
+        }
+        let is_new = self.steps[index] != self.step;
+        self.steps[index] = self.step;
+        is_new
+    }
+}
+
+// An automaton the leftmost search can run on, a thread being the state reached by the search
+// started at a given position
+pub(crate) trait Search {
+    type State;
+
+    // The start state, `None` when no match can start anywhere
+    fn get_start(&mut self) -> Option<Self::State>;
+
+    fn is_final(&self, state: &Self::State) -> bool;
+
+    // Moves every thread on `alphabet` in order, dropping the threads that can no longer reach
+    // a final state and the ones that reach a state marked by an earlier thread
+    fn step(&mut self, threads: &mut Vec<(Self::State, usize)>, alphabet: char, marks: &mut Marks);
+}
+
+// Runs the searches started at every position at once, in a single pass over the text. The
+// threads are kept in the order of their start positions, and a thread that reaches the state
+// of an earlier one is dropped: both have the same future and the leftmost match wins. No
+// thread starts once a match is found, and the search ends when no thread is left that could
+// start further left or, for the longest match, extend it.
+pub(crate) fn find_leftmost<S: Search>(
+    search: &mut S,
+    text: &str,
+    match_kind: MatchKind,
+    marks: &mut Marks,
+) -> Option<Range<usize>> {
+    let mut threads: Vec<(S::State, usize)> = Vec::new();
+    let mut found: Option<Range<usize>> = None;
+    let positions = text
+        .char_indices()
+        .map(|(position, ch)| (position, Some(ch)))
+        .chain([(text.len(), None)]);
+    for (position, ch) in positions {
+        if found.is_none() {
+            threads.push((search.get_start()?, position));
+        }
+        // The first final thread started leftmost, so the threads after it cannot win any more,
+        // and neither can it once its shortest match is found
+        if let Some(index) = threads.iter().position(|(state, _)| search.is_final(state)) {
+            found = Some(threads[index].1..position);
+            threads.truncate(match match_kind {
+                MatchKind::LeftmostLongest => index + 1,
+                MatchKind::Shortest => index,
+                MatchKind::LeftmostFirst => unreachable!("Refused by check_match_kind"),
+            });
+        }
+        let Some(ch) = ch else {
+            break;
+        };
+        if threads.is_empty() {
+            if found.is_some() {
+                break;
+            }
+            continue;
+        }
+        marks.next_step();
+        search.step(&mut threads, ch, marks);
+    }
+    found
+}
+
+// The matching engines a `MatchIterator` can drive
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Searcher<'d> {
+    Ranges(&'d DFA),
+    Compiled(Cow<'d, OptimizedDFA<'d>>),
+    Lazy(&'d mut LazyDFA),
+    Pike(&'d PikeVM),
+    // The priority ordered program of a `Regex`, for its leftmost-first matches
+    Program(&'d CaptureProgram),
+}
+
+impl Searcher<'_> {
+    fn find(
+        &mut self,
+        text: &str,
+        match_kind: MatchKind,
+        marks: &mut Marks,
+    ) -> Option<Range<usize>> {
+        match self {
+            Self::Ranges(dfa) => find_leftmost(dfa, text, match_kind, marks),
+            Self::Compiled(dfa) => find_leftmost(&mut dfa.as_ref(), text, match_kind, marks),
+            Self::Lazy(dfa) => dfa.find_with_match_kind(text, match_kind, marks),
+            Self::Pike(vm) => find_leftmost(vm, text, match_kind, marks),
+            Self::Program(program) => program.find_first(text),
+        }
+    }
+}
+
+pub struct MatchIterator<'d, 't> {
+    searcher: Searcher<'d>,
+    text: &'t str,
     start: usize,
+    match_kind: MatchKind,
+    marks: Marks,
+}
+
+impl<'d, 't> MatchIterator<'d, 't> {
+    pub(crate) fn new(searcher: Searcher<'d>, text: &'t str, match_kind: MatchKind) -> Self {
+        Self {
+            searcher,
+            text,
+            start: 0,
+            match_kind,
+            marks: Marks::default(),
+        }
+    }
+
+    pub fn get_match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Fails with [`CustomError::UnsupportedMatchKind`] for [`MatchKind::LeftmostFirst`], which
+    /// only the iterators of a [`Regex`] follow.
+    pub fn set_match_kind(&mut self, match_kind: MatchKind) -> Result<(), CustomError> {
+        self.match_kind = check_match_kind(match_kind)?;
+        Ok(())
+    }
 }
 
-impl<'a> Iterator for MatchIterator<'a> {
+impl Iterator for MatchIterator<'_, '_> {
     type Item = Range<usize>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.start >= self.text.len() {
+        // The end of the text is searched as well, for the empty match it may hold
+        if self.start > self.text.len() {
             return None;
         }
-        if let Some(mut slice_index) = self.dfa.find(&self.text[self.start..]).unwrap() {
+        if let Some(mut slice_index) =
+            self.searcher
+                .find(&self.text[self.start..], self.match_kind, &mut self.marks)
+        {
             slice_index.start += self.start;
             slice_index.end += self.start;
-            self.start = slice_index.end.max(self.start + 1);
+            // Step over one whole character after an empty match to stay on a char boundary
+            self.start = if slice_index.end > self.start {
+                slice_index.end
+            } else {
+                self.start
+                    + self.text[self.start..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8)
+            };
             return Some(slice_index);
         }
+        self.start = self.text.len() + 1;
         None
     }
 }
 
+// A character has to lead to a single state, so the ranges of the alphabet are disjoint
+fn check_alphabets(alphabets: &HashSet<CharRange>) -> Result<(), CustomError> {
+    if alphabets
+        .iter()
+        .sorted_unstable()
+        .tuple_windows()
+        .any(|(range1, range2)| range1.get_end() >= range2.get_start())
+    {
+        return Err(CustomError::InvalidAlphabet);
+    }
+    Ok(())
+}
+
+// Gathers the transitions of every state into its range map, rejecting overlapping ranges after
+// overlapping alphabets
+pub(crate) fn group_transitions(
+    alphabets: &HashSet<CharRange>,
+    transitions: impl IntoIterator<Item = ((State, CharRange), State)>,
+) -> Result<HashMap<State, RangeMap<State>>, CustomError> {
+    check_alphabets(alphabets)?;
+    let mut grouped: HashMap<State, Vec<(CharRange, State)>> = HashMap::new();
+    for ((from, alphabet), to) in transitions {
+        grouped.entry(from).or_default().push((alphabet, to));
+    }
+    grouped
+        .into_iter()
+        .map(|(from, mut ranges)| {
+            ranges.sort_unstable();
+            if ranges
+                .iter()
+                .tuple_windows()
+                .any(|((range1, _), (range2, _))| range1.get_end() >= range2.get_start())
+            {
+                return Err(CustomError::InvalidDFAKeys);
+            }
+            Ok((from, RangeMap::from_sorted(ranges, None)))
+        })
+        .collect()
+}
+
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub struct DFA {
     states: HashSet<State>,
-    alphabets: HashSet<char>,
-    transition_table: HashMap<(State, char), State>,
+    alphabets: HashSet<CharRange>,
+    transition_table: HashMap<State, RangeMap<State>>,
     start_state: State,
     final_states: HashSet<State>,
     trapped_states: HashSet<State>,
+    match_kind: MatchKind,
+    provenance: Option<HashMap<State, Provenance>>,
 }
 
 impl DFA {
-    pub fn new_unoptimized(
+    pub fn new_unoptimized<A: Into<CharRange>>(
+        states: HashSet<State>,
+        alphabets: HashSet<A>,
+        transition_table: HashMap<(State, A), State>,
+        start_state: State,
+        final_states: HashSet<State>,
+    ) -> Result<Self, CustomError> {
+        let alphabets = alphabets.into_iter().map(Into::into).collect();
+        let transition_table = group_transitions(
+            &alphabets,
+            transition_table
+                .into_iter()
+                .map(|((from, alphabet), to)| ((from, alphabet.into()), to)),
+        )?;
+        Self::new_unoptimized_with_provenance(
+            states,
+            alphabets,
+            transition_table,
+            start_state,
+            final_states,
+            None,
+        )
+    }
+
+    pub fn new<A: Into<CharRange>>(
         states: HashSet<State>,
-        alphabets: HashSet<char>,
-        transition_table: HashMap<(State, char), State>,
+        alphabets: HashSet<A>,
+        transition_table: HashMap<(State, A), State>,
         start_state: State,
         final_states: HashSet<State>,
     ) -> Result<Self, CustomError> {
+        let alphabets = alphabets.into_iter().map(Into::into).collect();
+        let transition_table = group_transitions(
+            &alphabets,
+            transition_table
+                .into_iter()
+                .map(|((from, alphabet), to)| ((from, alphabet.into()), to)),
+        )?;
+        Self::new_with_provenance(
+            states,
+            alphabets,
+            transition_table,
+            start_state,
+            final_states,
+            None,
+        )
+    }
+
+    pub fn from_range_maps_unoptimized(
+        states: HashSet<State>,
+        alphabets: HashSet<CharRange>,
+        transition_table: HashMap<State, RangeMap<State>>,
+        start_state: State,
+        final_states: HashSet<State>,
+    ) -> Result<Self, CustomError> {
+        Self::new_unoptimized_with_provenance(
+            states,
+            alphabets,
+            transition_table,
+            start_state,
+            final_states,
+            None,
+        )
+    }
+
+    /// Builds the DFA from the [`RangeMap`] of every state, whose default edge reads the
+    /// characters outside the alphabet. The ranges of every state have to cover the alphabet
+    /// exactly, as checked by [`DFA::check_validity`].
+    ///
+    /// ```
+    /// use re_rs::{compile_regex, CharRange, RangeMap, State, DFA};
+    /// use std::collections::{HashMap, HashSet};
+    ///
+    /// // Any character but a newline
+    /// let [q0, q1, q2] = [0, 1, 2].map(State::new);
+    /// let transitions = |default: State| {
+    ///     let mut transitions = RangeMap::new();
+    ///     transitions.insert('\n', q2);
+    ///     transitions.set_default(Some(default));
+    ///     transitions
+    /// };
+    /// let dfa = DFA::from_range_maps(
+    ///     HashSet::from([q0, q1, q2]),
+    ///     HashSet::from([CharRange::from('\n')]),
+    ///     HashMap::from([(q0, transitions(q1)), (q1, transitions(q2)), (q2, transitions(q2))]),
+    ///     q0,
+    ///     HashSet::from([q1]),
+    /// )
+    /// .unwrap();
+    /// assert!(dfa.accepts("x") && dfa.accepts("\u{10ffff}") && !dfa.accepts("\n"));
+    /// assert!(dfa.is_equivalent(&compile_regex(".").unwrap()));
+    ///
+    /// // Minimization turns the widest ranges back into default edges
+    /// let minimized = compile_regex(".").unwrap().get_minimized();
+    /// assert_eq!(minimized.get_alphabets(), &HashSet::from([CharRange::from('\n')]));
+    /// assert!(minimized.is_isomorphic(&dfa));
+    /// ```
+    pub fn from_range_maps(
+        states: HashSet<State>,
+        alphabets: HashSet<CharRange>,
+        transition_table: HashMap<State, RangeMap<State>>,
+        start_state: State,
+        final_states: HashSet<State>,
+    ) -> Result<Self, CustomError> {
+        Self::new_with_provenance(
+            states,
+            alphabets,
+            transition_table,
+            start_state,
+            final_states,
+            None,
+        )
+    }
+
+    fn new_unoptimized_with_provenance(
+        states: HashSet<State>,
+        alphabets: HashSet<CharRange>,
+        mut transition_table: HashMap<State, RangeMap<State>>,
+        start_state: State,
+        final_states: HashSet<State>,
+        provenance: Option<HashMap<State, Provenance>>,
+    ) -> Result<Self, CustomError> {
+        // States without any transition, as with an empty alphabet, get an empty range map
+        for &state in &states {
+            transition_table.entry(state).or_default();
+        }
         let mut dfa = Self {
             states,
             alphabets,
@@ -169,9 +810,12 @@ impl DFA {
             start_state,
             final_states,
             trapped_states: HashSet::default(),
+            match_kind: MatchKind::default(),
+            provenance,
         };
         dfa.check_validity()?;
-        if AUTO_OPTIMIZE {
+        // Minimization would merge the states the provenance table describes
+        if AUTO_OPTIMIZE && dfa.provenance.is_none() {
             dfa.minimize();
         } else {
             dfa.update_trapped_states();
@@ -179,21 +823,23 @@ impl DFA {
         Ok(dfa)
     }
 
-    pub fn new(
+    pub(crate) fn new_with_provenance(
         states: HashSet<State>,
-        alphabets: HashSet<char>,
-        transition_table: HashMap<(State, char), State>,
+        alphabets: HashSet<CharRange>,
+        transition_table: HashMap<State, RangeMap<State>>,
         start_state: State,
         final_states: HashSet<State>,
+        provenance: Option<HashMap<State, Provenance>>,
     ) -> Result<Self, CustomError> {
-        let mut dfa = Self::new_unoptimized(
+        let mut dfa = Self::new_unoptimized_with_provenance(
             states,
             alphabets,
             transition_table,
             start_state,
             final_states,
+            provenance,
         )?;
-        if AUTO_OPTIMIZE {
+        if AUTO_OPTIMIZE && dfa.provenance.is_none() {
             dfa.minimize();
         }
         Ok(dfa)
@@ -206,21 +852,21 @@ impl DFA {
             reachable_states.insert(state);
             stack.push(state);
         }
-        let mut reversed_transition_table: HashMap<(State, char), HashSet<State>> = HashMap::new();
-        for (&(from, alphabet), &to) in &self.transition_table {
-            reversed_transition_table
-                .entry((to, alphabet))
-                .or_default()
-                .insert(from);
+        let mut reversed_transition_table: HashMap<State, HashSet<State>> = HashMap::new();
+        for (&from, transitions) in &self.transition_table {
+            for &to in transitions.values() {
+                reversed_transition_table
+                    .entry(to)
+                    .or_default()
+                    .insert(from);
+            }
         }
         while let Some(state) = stack.pop() {
-            for &alphabet in &self.alphabets {
-                if let Some(prev_states) = reversed_transition_table.get(&(state, alphabet)) {
-                    for &prev_state in prev_states {
-                        if !reachable_states.contains(&prev_state) {
-                            reachable_states.insert(prev_state);
-                            stack.push(prev_state);
-                        }
+            if let Some(prev_states) = reversed_transition_table.get(&state) {
+                for &prev_state in prev_states {
+                    if !reachable_states.contains(&prev_state) {
+                        reachable_states.insert(prev_state);
+                        stack.push(prev_state);
                     }
                 }
             }
@@ -235,8 +881,8 @@ impl DFA {
 
         while let Some(state) = stack.pop() {
             reachable_states.insert(state);
-            for &alphabet in &self.alphabets {
-                if let Some(&next_state) = self.transition_table.get(&(state, alphabet)) {
+            if let Some(transitions) = self.transition_table.get(&state) {
+                for &next_state in transitions.values() {
                     if !reachable_states.contains(&next_state) {
                         stack.push(next_state);
                     }
@@ -245,13 +891,15 @@ impl DFA {
         }
 
         self.states.retain(|state| reachable_states.contains(state));
-        self.transition_table.retain(|(from, _), to| {
-            reachable_states.contains(from) && reachable_states.contains(to)
-        });
+        self.transition_table
+            .retain(|from, _| reachable_states.contains(from));
         self.final_states
             .retain(|state| reachable_states.contains(state));
         self.trapped_states
             .retain(|state| reachable_states.contains(state));
+        if let Some(provenance) = &mut self.provenance {
+            provenance.retain(|state, _| reachable_states.contains(state));
+        }
     }
 
     // DFS helper function for topological sorting
@@ -274,8 +922,12 @@ impl DFA {
         let mut visited = HashSet::new();
         let mut stack = Vec::new();
 
-        // Assuming `self.states` is accessible & iterable
-        for &state in &self.states {
+        // Start from the start state and visit the rest in order, so that the result only
+        // depends on the structure of the automaton and not on hashing
+        for state in [self.start_state]
+            .into_iter()
+            .chain(self.states.iter().copied().sorted_unstable())
+        {
             if !visited.contains(&state) {
                 self.dfs(state, &mut visited, &mut stack);
             }
@@ -288,14 +940,10 @@ impl DFA {
     // Simplified function to get adjacent states from a given state
     fn get_adjacent_states(&self, state: &State) -> Vec<State> {
         self.transition_table
-            .iter()
-            .filter_map(|((from_state, _), &to_state)| {
-                if from_state == state {
-                    Some(to_state)
-                } else {
-                    None
-                }
-            })
+            .get(state)
+            .into_iter()
+            .flat_map(RangeMap::values)
+            .copied()
             .collect()
     }
 
@@ -317,7 +965,7 @@ impl DFA {
                 )
             })
             .enumerate()
-            .map(|(index, &state)| (state, State::new(DEFAULT_STATE_SYMBOL, index)))
+            .map(|(index, &state)| (state, State::new(index)))
             .collect::<HashMap<_, _>>();
         self.states = self
             .states
@@ -327,8 +975,11 @@ impl DFA {
         self.transition_table = self
             .transition_table
             .iter()
-            .map(|(&(from, alphabet), &to)| {
-                ((states_mapping[&from], alphabet), states_mapping[&to])
+            .map(|(from, transitions)| {
+                (
+                    states_mapping[from],
+                    transitions.map(|to| states_mapping[to]),
+                )
             })
             .collect();
         self.start_state = states_mapping[&self.start_state];
@@ -342,20 +993,86 @@ impl DFA {
             .iter()
             .map(|&state| states_mapping[&state])
             .collect();
+        self.provenance = self.provenance.take().map(|provenance| {
+            provenance
+                .into_iter()
+                .map(|(state, origin)| (states_mapping[&state], origin))
+                .collect()
+        });
     }
 
     pub fn get_states(&self) -> &HashSet<State> {
         &self.states
     }
 
-    pub fn get_alphabets(&self) -> &HashSet<char> {
+    pub fn get_alphabets(&self) -> &HashSet<CharRange> {
         &self.alphabets
     }
 
-    pub fn get_transition_table(&self) -> &HashMap<(State, char), State> {
+    pub fn get_transition_table(&self) -> &HashMap<State, RangeMap<State>> {
         &self.transition_table
     }
 
+    /// The state reached from `state` on `alphabet`, through the default edge when `alphabet`
+    /// is outside the alphabet, if there is a transition at all.
+    pub fn get_next_state(&self, state: State, alphabet: char) -> Option<State> {
+        let transitions = self.transition_table.get(&state)?;
+        transitions
+            .get(alphabet)
+            .or(transitions.get_default())
+            .copied()
+    }
+
+    // The alphabet split at the ends of the ranges of every state, so that each state moves the
+    // same way on all the characters of an atom
+    pub(crate) fn get_atoms(&self) -> Vec<CharRange> {
+        split_into_atoms(
+            self.alphabets.iter().copied().chain(
+                self.transition_table.values().flat_map(|transitions| {
+                    transitions.get_ranges().iter().map(|&(range, _)| range)
+                }),
+            ),
+        )
+    }
+
+    // The transitions of `state` over sorted ranges, the default edge spelled out as the ranges
+    // outside the alphabet that it reads
+    pub(crate) fn get_explicit_transitions(&self, state: State) -> Vec<(CharRange, State)> {
+        let transitions = &self.transition_table[&state];
+        let default_transitions = transitions.get_default().into_iter().flat_map(|&to| {
+            complement_ranges(self.alphabets.iter().copied())
+                .into_iter()
+                .map(move |range| (range, to))
+        });
+        transitions
+            .get_ranges()
+            .iter()
+            .copied()
+            .chain(default_transitions)
+            .sorted_unstable()
+            .collect()
+    }
+
+    pub(crate) fn has_default_transitions(&self) -> bool {
+        self.transition_table
+            .values()
+            .any(|transitions| transitions.get_default().is_some())
+    }
+
+    // A character of every atom, and one outside of the alphabet when default edges read it
+    fn get_symbols(&self) -> Vec<char> {
+        let atoms = self.get_atoms();
+        let default = self
+            .has_default_transitions()
+            .then(|| get_first_outside(&atoms))
+            .flatten();
+        atoms
+            .iter()
+            .map(|atom| atom.get_start())
+            .chain(default)
+            .collect()
+    }
+
     pub fn get_start_state(&self) -> &State {
         &self.start_state
     }
@@ -368,16 +1085,146 @@ impl DFA {
         &self.trapped_states
     }
 
+    /// Maps the states built by [`EpsilonNFA::to_dfa`] and the product constructions back to the
+    /// states they were made from, and is `None` for any other DFA.
+    ///
+    /// The table is only kept while the DFA is not minimized, as minimization merges states.
+    /// The constructions above therefore never minimize their result on their own, in debug and
+    /// release builds alike, while the other constructors may. Call [`DFA::minimize`] once the
+    /// table is no longer needed, which clears it.
+    ///
+    /// ```
+    /// use re_rs::parse_regex;
+    ///
+    /// let dfa = parse_regex("a|b").unwrap().to_dfa();
+    /// assert_eq!(dfa.get_provenance().unwrap().len(), dfa.get_states().len());
+    /// assert!(dfa.get_minimized().get_provenance().is_none());
+    /// ```
+    pub fn get_provenance(&self) -> Option<&HashMap<State, Provenance>> {
+        self.provenance.as_ref()
+    }
+
+    pub fn get_match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Fails with [`CustomError::UnsupportedMatchKind`] for [`MatchKind::LeftmostFirst`].
+    pub fn set_match_kind(&mut self, match_kind: MatchKind) -> Result<(), CustomError> {
+        self.match_kind = check_match_kind(match_kind)?;
+        Ok(())
+    }
+
+    /// Writes the DFA in the text format read by its [`FromStr`] implementation, with states,
+    /// alphabets and transitions sorted so that the output is stable.
+    ///
+    /// ```
+    /// use re_rs::{compile_regex, DFA};
+    ///
+    /// let dfa = compile_regex("ab*a").unwrap();
+    /// let text = dfa.to_text();
+    /// let parsed: DFA = text.parse().unwrap();
+    /// assert!(parsed.is_isomorphic(&dfa));
+    /// assert_eq!(parsed.to_text(), text);
+    /// ```
+    pub fn to_text(&self) -> String {
+        Definition::from(self).to_string()
+    }
+
+    /// Reads a finite automaton saved by JFLAP (`.jff`). Every state needs a transition for
+    /// every alphabet, as checked by [`DFA::check_validity`], and empty-string transitions,
+    /// several initial states or several targets for the same state and alphabet are reported
+    /// as [`CustomError::InvalidFormat`] at the offending element.
+    ///
+    /// ```
+    /// use re_rs::{CustomError, DFA};
+    ///
+    /// let jff = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+    /// <structure>
+    ///     <type>fa</type>
+    ///     <automaton>
+    ///         <state id="0" name="q0"><initial/></state>
+    ///         <state id="1" name="q1"><final/></state>
+    ///         <transition><from>0</from><to>1</to><read>a</read></transition>
+    ///         <transition><from>1</from><to>1</to><read>a</read></transition>
+    ///     </automaton>
+    /// </structure>"#;
+    /// let dfa = DFA::from_jff(jff).unwrap();
+    /// assert!(dfa.accepts("aa"));
+    /// assert!(DFA::from_jff(&dfa.to_jff().unwrap()).unwrap().is_isomorphic(&dfa));
+    ///
+    /// let partial = jff.replace("<from>1</from><to>1</to><read>a", "<from>1</from><to>1</to><read>b");
+    /// assert_eq!(DFA::from_jff(&partial).err(), Some(CustomError::InvalidDFAKeys));
+    /// ```
+    pub fn from_jff(text: &str) -> Result<Self, CustomError> {
+        parse_jff(text, true)?.into_dfa()
+    }
+
+    /// Writes the DFA as a JFLAP finite automaton (`.jff`), with the states laid out on a row.
+    ///
+    /// JFLAP has no character ranges, so each range is written as a transition per character,
+    /// a range of more than 256 characters is refused with [`CustomError::UnsupportedJFFRange`]
+    /// and a default edge, as from `.` or `[^a]`, with [`CustomError::UnsupportedJFFDefault`].
+    ///
+    /// ```
+    /// use re_rs::{compile_regex, CharRange, CustomError, DFA};
+    ///
+    /// let word = compile_regex("\\w+").unwrap();
+    /// assert!(DFA::from_jff(&word.to_jff().unwrap()).unwrap().is_isomorphic(&word));
+    ///
+    /// let range = CharRange::from('一'..='龥');
+    /// let han = compile_regex("[一-龥]").unwrap();
+    /// assert_eq!(han.to_jff().err(), Some(CustomError::UnsupportedJFFRange { range }));
+    ///
+    /// // Two characters around the surrogate code points
+    /// let around = compile_regex("[\u{D7FF}-\u{E000}]").unwrap();
+    /// assert!(DFA::from_jff(&around.to_jff().unwrap()).unwrap().is_isomorphic(&around));
+    ///
+    /// let any = compile_regex("a.b").unwrap();
+    /// assert_eq!(any.to_jff().err(), Some(CustomError::UnsupportedJFFDefault));
+    /// ```
+    pub fn to_jff(&self) -> Result<String, CustomError> {
+        format_jff(Definition::from(self))
+    }
+
+    /// Serializes the compiled matcher of the DFA in little endian byte order, to be loaded
+    /// without copying by [`SerializedDFA::from_bytes`]. Minimize the DFA first to get the
+    /// smallest table.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        write_dfa(&self.into(), false)
+    }
+
+    /// Same as [`DFA::to_le_bytes`] in big endian byte order.
+    pub fn to_be_bytes(&self) -> Vec<u8> {
+        write_dfa(&self.into(), true)
+    }
+
+    /// Same as [`DFA::to_le_bytes`] in the native byte order.
+    pub fn to_ne_bytes(&self) -> Vec<u8> {
+        write_dfa(&self.into(), cfg!(target_endian = "big"))
+    }
+
     pub fn add_state(&mut self, state: State) {
         self.states.insert(state);
+        self.transition_table.entry(state).or_default();
     }
 
-    pub fn add_alphabet(&mut self, alphabet: char) {
-        self.alphabets.insert(alphabet);
+    pub fn add_alphabet(&mut self, alphabet: impl Into<CharRange>) {
+        self.alphabets.insert(alphabet.into());
     }
 
-    pub fn add_transition(&mut self, from: State, to: State, alphabet: char) {
-        self.transition_table.insert((from, alphabet), to);
+    pub fn add_transition(&mut self, from: State, to: State, alphabet: impl Into<CharRange>) {
+        self.transition_table
+            .entry(from)
+            .or_default()
+            .insert(alphabet, to);
+    }
+
+    /// Sends every character outside the alphabet from `from` to `to`.
+    pub fn add_default_transition(&mut self, from: State, to: State) {
+        self.transition_table
+            .entry(from)
+            .or_default()
+            .set_default(Some(to));
     }
 
     pub fn set_start_state(&mut self, state: State) {
@@ -389,16 +1236,19 @@ impl DFA {
     }
 
     pub fn check_validity(&self) -> Result<(), CustomError> {
-        let mut expected_keys = self
-            .states
-            .iter()
-            .copied()
-            .cartesian_product(self.alphabets.iter().copied())
-            .collect_vec();
-        let mut actual_keys = self.transition_table.keys().copied().collect_vec();
-        expected_keys.sort_unstable();
-        actual_keys.sort_unstable();
-        if expected_keys != actual_keys {
+        check_alphabets(&self.alphabets)?;
+        // Every state reads the whole alphabet through its ranges and nothing more, the default
+        // edge reading the rest, and every edge leads to one of the states
+        let alphabets = normalize_ranges(self.alphabets.iter().copied());
+        if self.transition_table.len() != self.states.len()
+            || self.states.iter().any(|state| {
+                self.transition_table.get(state).is_none_or(|transitions| {
+                    normalize_ranges(transitions.get_ranges().iter().map(|&(range, _)| range))
+                        != alphabets
+                        || transitions.values().any(|to| !self.states.contains(to))
+                })
+            })
+        {
             return Err(CustomError::InvalidDFAKeys);
         }
         if !self.states.contains(&self.start_state) {
@@ -410,16 +1260,100 @@ impl DFA {
         Ok(())
     }
 
+    /// Minimizes the DFA with Hopcroft's partition refinement in `O(n k log n)`.
     pub fn minimize(&mut self) {
+        self.complete_default_transitions();
+        self.remove_unreachable_states();
+        let partition = self.get_hopcroft_partition();
+        self.merge_partition(partition);
+    }
+
+    /// Minimizes the DFA by refining the whole partition until it is stable, which is roughly
+    /// cubic. It is kept to cross-check [`DFA::minimize`]:
+    ///
+    /// ```
+    /// use re_rs::parse_regex;
+    ///
+    /// let patterns = ["ab*a", "a|ab*a", "(a|b)*abb", "(a|b)*a(a|b)(a|b)", "((ab)*|c)+d?", ""];
+    /// let dfas = patterns.map(|pattern| parse_regex(pattern).unwrap().to_dfa());
+    /// let products = dfas.iter().flat_map(|dfa1| {
+    ///     dfas.iter().flat_map(move |dfa2| {
+    ///         [dfa1.union(dfa2), dfa1.intersection(dfa2), dfa1.difference(dfa2)]
+    ///     })
+    /// });
+    ///
+    /// for dfa in dfas.iter().cloned().chain(products.map(Result::unwrap)) {
+    ///     let mut hopcroft = dfa.clone();
+    ///     hopcroft.minimize();
+    ///     let mut naive = dfa.clone();
+    ///     naive.minimize_naive();
+    ///     assert!(hopcroft.is_isomorphic(&naive), "{dfa}");
+    /// }
+    /// ```
+    pub fn minimize_naive(&mut self) {
+        self.complete_default_transitions();
         self.remove_unreachable_states();
+        let partition = self.get_naive_partition();
+        self.merge_partition(partition);
+    }
 
-        let mut partition = vec![
+    // The partitions need a transition on every symbol, so when only some states have a default
+    // edge, the others get one to a new trap state. Default edges that no character reads are
+    // dropped instead.
+    fn complete_default_transitions(&mut self) {
+        let alphabets = self
+            .alphabets
+            .iter()
+            .copied()
+            .sorted_unstable()
+            .collect_vec();
+        if get_first_outside(&alphabets).is_none() {
+            for transitions in self.transition_table.values_mut() {
+                transitions.set_default(None);
+            }
+            return;
+        }
+        let defaults_count = self
+            .transition_table
+            .values()
+            .filter(|transitions| transitions.get_default().is_some())
+            .count();
+        if defaults_count == 0 || defaults_count == self.transition_table.len() {
+            return;
+        }
+        let trap_state = State::new(
+            self.states
+                .iter()
+                .map(|state| state.get_index() + 1)
+                .max()
+                .unwrap_or_default(),
+        );
+        for transitions in self.transition_table.values_mut() {
+            if transitions.get_default().is_none() {
+                transitions.set_default(Some(trap_state));
+            }
+        }
+        self.states.insert(trap_state);
+        self.trapped_states.insert(trap_state);
+        self.transition_table.insert(
+            trap_state,
+            RangeMap::from_sorted(
+                alphabets.into_iter().map(|alphabet| (alphabet, trap_state)),
+                Some(trap_state),
+            ),
+        );
+    }
+
+    fn get_naive_partition(&self) -> Vec<HashSet<State>> {
+        let symbols = self.get_symbols();
+        let mut partition: Vec<HashSet<State>> = vec![
             self.final_states.clone(),
             self.states
                 .difference(&self.final_states)
                 .cloned()
                 .collect(),
         ];
+        partition.retain(|group| !group.is_empty());
         let mut refined = true;
 
         while refined {
@@ -431,11 +1365,11 @@ impl DFA {
 
                 for &state in group {
                     if let Some(index) = split_sets.iter().position(|set| {
-                        self.alphabets.iter().all(|&ch| {
+                        symbols.iter().all(|&ch| {
                             let set_representative = *set.iter().next().unwrap();
-                            let next_state = self.transition_table[&(set_representative, ch)];
+                            let next_state = self.get_next_state(set_representative, ch).unwrap();
                             partition.iter().any(|group| {
-                                group.contains(&self.transition_table[&(state, ch)])
+                                group.contains(&self.get_next_state(state, ch).unwrap())
                                     && group.contains(&next_state)
                             })
                         })
@@ -455,16 +1389,123 @@ impl DFA {
             }
             partition = new_partition;
         }
+        partition
+    }
+
+    fn get_hopcroft_partition(&self) -> Vec<HashSet<State>> {
+        let states = self.states.iter().copied().sorted_unstable().collect_vec();
+        let alphabets = self.get_symbols();
+        let state_index: HashMap<State, usize> = states
+            .iter()
+            .enumerate()
+            .map(|(index, &state)| (state, index))
+            .collect();
+
+        // reversed_transition_table[alphabet][to] lists every state moving to `to` on `alphabet`
+        let mut reversed_transition_table = vec![vec![Vec::new(); states.len()]; alphabets.len()];
+        for (alphabet_index, &alphabet) in alphabets.iter().enumerate() {
+            for (from, &state) in states.iter().enumerate() {
+                let to = state_index[&self.get_next_state(state, alphabet).unwrap()];
+                reversed_transition_table[alphabet_index][to].push(from);
+            }
+        }
+
+        // Blocks are contiguous ranges of `elements`, so a block is split by moving its marked
+        // states to the front of its range, in time proportional to the marked states only
+        let mut elements = (0..states.len()).collect_vec();
+        elements.sort_unstable_by_key(|&index| !self.final_states.contains(&states[index]));
+        let mut location = vec![0; states.len()];
+        for (position, &index) in elements.iter().enumerate() {
+            location[index] = position;
+        }
+        let final_count = self.final_states.len();
+        let mut block_ranges = [0..final_count, final_count..states.len()]
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect_vec();
+        let mut block_of = vec![0; states.len()];
+        for (block, range) in block_ranges.iter().enumerate() {
+            for &index in &elements[range.clone()] {
+                block_of[index] = block;
+            }
+        }
+        let mut marked_count = vec![0; block_ranges.len()];
+
+        let mut in_worklist = vec![vec![false; alphabets.len()]; block_ranges.len()];
+        let mut worklist = Vec::new();
+        if block_ranges.len() == 2 {
+            let smaller = if block_ranges[0].len() <= block_ranges[1].len() {
+                0
+            } else {
+                1
+            };
+            in_worklist[smaller].fill(true);
+            worklist.extend((0..alphabets.len()).map(|alphabet_index| (smaller, alphabet_index)));
+        }
 
+        while let Some((splitter, alphabet_index)) = worklist.pop() {
+            in_worklist[splitter][alphabet_index] = false;
+            let mut touched_blocks = Vec::new();
+            let splitter_states = elements[block_ranges[splitter].clone()].to_vec();
+            for to in splitter_states {
+                for &from in &reversed_transition_table[alphabet_index][to] {
+                    let block = block_of[from];
+                    if marked_count[block] == 0 {
+                        touched_blocks.push(block);
+                    }
+                    let marked_position = block_ranges[block].start + marked_count[block];
+                    let swapped = elements[marked_position];
+                    elements.swap(location[from], marked_position);
+                    location[swapped] = location[from];
+                    location[from] = marked_position;
+                    marked_count[block] += 1;
+                }
+            }
+            for block in touched_blocks {
+                let marked = std::mem::take(&mut marked_count[block]);
+                if marked == block_ranges[block].len() {
+                    continue;
+                }
+                let range = block_ranges[block].clone();
+                let new_block = block_ranges.len();
+                block_ranges.push(range.start..range.start + marked);
+                block_ranges[block] = range.start + marked..range.end;
+                marked_count.push(0);
+                for &index in &elements[block_ranges[new_block].clone()] {
+                    block_of[index] = new_block;
+                }
+                in_worklist.push(vec![false; alphabets.len()]);
+                let smaller = if block_ranges[new_block].len() <= block_ranges[block].len() {
+                    new_block
+                } else {
+                    block
+                };
+                let pending = in_worklist[block].clone();
+                for (alphabet_index, pending) in pending.into_iter().enumerate() {
+                    let added = if pending { new_block } else { smaller };
+                    in_worklist[added][alphabet_index] = true;
+                    worklist.push((added, alphabet_index));
+                }
+            }
+        }
+
+        block_ranges
+            .into_iter()
+            .map(|range| elements[range].iter().map(|&index| states[index]).collect())
+            .collect()
+    }
+
+    fn merge_partition(&mut self, partition: Vec<HashSet<State>>) {
         let mut new_states = HashSet::new();
         let mut new_transition_table = HashMap::new();
         let mut new_final_states = HashSet::new();
 
         let group_representative: HashMap<State, State> =
             HashMap::from_iter(partition.iter().flat_map(|group| {
+                let representative_state = *group.iter().min().unwrap();
                 group
                     .iter()
-                    .map(|&state| (state, *group.iter().min().unwrap()))
+                    .map(move |&state| (state, representative_state))
             }));
 
         for group in &partition {
@@ -479,127 +1520,435 @@ impl DFA {
                 new_final_states.insert(representative_state);
             }
 
-            for &alphabet in &self.alphabets {
-                let next_state = self.transition_table[&(representative_state, alphabet)];
-                let partition_index = partition
-                    .iter()
-                    .position(|s| s.contains(&next_state))
-                    .unwrap();
-                let partition_state =
-                    group_representative[partition[partition_index].iter().min().unwrap()];
-                new_transition_table.insert((representative_state, alphabet), partition_state);
-            }
+            new_transition_table.insert(
+                representative_state,
+                self.transition_table[&representative_state].map(|to| group_representative[to]),
+            );
         }
 
         self.states = new_states;
         self.transition_table = new_transition_table;
         self.final_states = new_final_states;
+        self.provenance = None;
         // Always update trapped states as we assumed trapped states are updated while creating new object
         self.update_trapped_states();
-        // Always rename states as we assumed states are renamed while creating the MatchIterator Method
+        self.compress_alphabet();
+        // The dead state may only have been reached on the atoms the compression dropped
+        self.remove_unreachable_states();
+        // Always rename states so that the minimized automaton uses dense IDs from the start state on
         self.rename_states();
     }
 
+    // Merges the neighbouring atoms on which every state moves the same way, and leaves the
+    // atoms that follow the default edges to them. When every character has a transition, or
+    // would only miss one to the dead state, the atoms with the most characters become the
+    // default edges, so that `.` or `[^x]` keep a single range.
+    fn compress_alphabet(&mut self) {
+        let states = self.states.iter().copied().sorted_unstable().collect_vec();
+        let column = |alphabet: char| {
+            states
+                .iter()
+                .map(|&state| self.get_next_state(state, alphabet))
+                .collect_vec()
+        };
+        let atoms = self.get_atoms();
+        let mut columns = atoms
+            .iter()
+            .map(|&atom| (atom, column(atom.get_start())))
+            .collect_vec();
+        let outside_ranges = complement_ranges(atoms.iter().copied());
+        // Without default edges, the characters outside the alphabet lead nowhere, just as they
+        // would to the dead state
+        let dead_state = self
+            .trapped_states
+            .iter()
+            .min()
+            .copied()
+            .filter(|_| !self.has_default_transitions());
+        let default_column = match (outside_ranges.first(), dead_state) {
+            (Some(range), None) => column(range.get_start()),
+            (_, dead_state) => {
+                let dead_column = vec![dead_state; states.len()];
+                columns.extend(
+                    outside_ranges
+                        .iter()
+                        .map(|&range| (range, dead_column.clone())),
+                );
+                columns.sort_unstable();
+                let mut widths: HashMap<&Vec<Option<State>>, usize> = HashMap::new();
+                for (atom, column) in &columns {
+                    *widths.entry(column).or_default() +=
+                        atom.get_end() as usize - atom.get_start() as usize + 1;
+                }
+                let widest_column = columns
+                    .iter()
+                    .map(|(_, column)| column)
+                    .max_by_key(|&column| widths[column])
+                    .cloned()
+                    .unwrap_or_default();
+                if dead_state.is_some() && widest_column == dead_column {
+                    // The characters outside the alphabet may as well keep leading nowhere
+                    columns.retain(|(_, column)| *column != dead_column);
+                    Vec::new()
+                } else {
+                    widest_column
+                }
+            }
+        };
+        let has_default = !default_column.is_empty() && default_column.iter().all(Option::is_some);
+        let mut ranges: Vec<(CharRange, &Vec<Option<State>>)> = Vec::new();
+        for (atom, column) in &columns {
+            if has_default && *column == default_column {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((range, last_column))
+                    if *last_column == column
+                        && next_char(range.get_end()) == Some(atom.get_start()) =>
+                {
+                    *range = CharRange::new(range.get_start(), atom.get_end());
+                }
+                _ => ranges.push((*atom, column)),
+            }
+        }
+        self.transition_table = states
+            .iter()
+            .enumerate()
+            .map(|(index, &state)| {
+                let transitions = RangeMap::from_sorted(
+                    ranges
+                        .iter()
+                        .map(|&(range, column)| (range, column[index].unwrap())),
+                    has_default.then(|| default_column[index].unwrap()),
+                );
+                (state, transitions)
+            })
+            .collect();
+        self.alphabets = ranges.into_iter().map(|(range, _)| range).collect();
+    }
+
+    /// Checks whether both DFAs are equal up to renaming their states. The alphabets have to
+    /// cover the same characters, but may split them into different ranges.
+    pub fn is_isomorphic(&self, other: &DFA) -> bool {
+        if self.states.len() != other.states.len() {
+            return false;
+        }
+        let (atoms, default) = self.get_common_atoms(other);
+        let alphabets = atoms
+            .iter()
+            .map(|atom| atom.get_start())
+            .chain(default)
+            .collect_vec();
+        let mut mapping = HashMap::from([(self.start_state, other.start_state)]);
+        let mut mapped_states = HashSet::from([other.start_state]);
+        let mut stack = vec![(self.start_state, other.start_state)];
+        while let Some((state1, state2)) = stack.pop() {
+            if self.final_states.contains(&state1) != other.final_states.contains(&state2) {
+                return false;
+            }
+            for &alphabet in &alphabets {
+                let (next_state1, next_state2) = match (
+                    self.get_next_state(state1, alphabet),
+                    other.get_next_state(state2, alphabet),
+                ) {
+                    (Some(next_state1), Some(next_state2)) => (next_state1, next_state2),
+                    (None, None) => continue,
+                    _ => return false,
+                };
+                match mapping.get(&next_state1) {
+                    Some(&mapped_state) if mapped_state != next_state2 => return false,
+                    Some(_) => {}
+                    None => {
+                        if !mapped_states.insert(next_state2) {
+                            return false;
+                        }
+                        mapping.insert(next_state1, next_state2);
+                        stack.push((next_state1, next_state2));
+                    }
+                }
+            }
+        }
+        mapping.len() == self.states.len()
+    }
+
     pub fn get_minimized(&self) -> Self {
         let mut dfa = self.clone();
         dfa.minimize();
         dfa
     }
 
-    fn product<'a>(
+    // Splits the characters read by either DFA into atoms on which both move the same way, with
+    // a character outside of both alphabets when either has default edges
+    fn get_common_atoms(&self, other: &DFA) -> (Vec<CharRange>, Option<char>) {
+        let atoms = split_into_atoms(self.get_atoms().into_iter().chain(other.get_atoms()));
+        let default = (self.has_default_transitions() || other.has_default_transitions())
+            .then(|| get_first_outside(&atoms))
+            .flatten();
+        (atoms, default)
+    }
+
+    // `None` on either side is the implicit trap reached on a character without a transition in
+    // that operand, so the other operand keeps running on its own. The characters outside of
+    // both alphabets go through the default edge of the product.
+    fn product(
         &self,
         other: &DFA,
-        possible_final_states: impl Iterator<Item = (State, State)>,
+        is_final: impl Fn(bool, bool) -> bool,
     ) -> Result<Self, CustomError> {
-        let mut expand_stack = vec![(self.start_state, other.start_state)];
-        let mut new_states = HashSet::new();
-        let new_alphabets: HashSet<char> =
-            HashSet::from_iter(self.alphabets.union(&other.alphabets).copied());
+        let (atoms, default) = self.get_common_atoms(other);
+        let start_pair = (Some(self.start_state), Some(other.start_state));
+        let start_state = State::new(0);
+        let mut new_states: HashMap<(Option<State>, Option<State>), State> =
+            HashMap::from([(start_pair, start_state)]);
+        let mut expand_stack = vec![(start_pair, start_state)];
         let mut new_transition_table = HashMap::new();
-        let trapped_state = (State::new('T', 0), State::new('T', 0));
-        new_states.insert(trapped_state);
-        for &alphabet in &new_alphabets {
-            new_transition_table.insert((trapped_state, alphabet), trapped_state);
-        }
-        while let Some(state) = expand_stack.pop() {
-            new_states.insert(state);
-            for &alphabet in &new_alphabets {
-                let next_state = if let (Some(&state1), Some(&state2)) = (
-                    self.transition_table.get(&(state.0, alphabet)),
-                    other.transition_table.get(&(state.1, alphabet)),
-                ) {
-                    (state1, state2)
-                } else {
-                    trapped_state
+        while let Some(((state1, state2), state)) = expand_stack.pop() {
+            let mut next_states = Vec::new();
+            for alphabet in atoms.iter().map(|atom| atom.get_start()).chain(default) {
+                let next_pair = (
+                    state1.and_then(|state| self.get_next_state(state, alphabet)),
+                    state2.and_then(|state| other.get_next_state(state, alphabet)),
+                );
+                let next_state = match new_states.get(&next_pair) {
+                    Some(&next_state) => next_state,
+                    None => {
+                        let next_state = State::new(new_states.len());
+                        new_states.insert(next_pair, next_state);
+                        expand_stack.push((next_pair, next_state));
+                        next_state
+                    }
                 };
-                new_transition_table.insert((state, alphabet), next_state);
-                if new_states.insert(next_state) {
-                    expand_stack.push(next_state);
-                }
+                next_states.push(next_state);
             }
+            let default_state = default.and_then(|_| next_states.pop());
+            new_transition_table.insert(
+                state,
+                RangeMap::from_sorted(atoms.iter().copied().zip(next_states), default_state),
+            );
         }
-        let new_states_combined: HashSet<State> = new_states
-            .into_iter()
-            .map(|(state1, state2)| state1.concat(state2))
-            .collect();
-        let new_final_states = possible_final_states
-            .filter_map(|(state1, state2)| {
-                let state = state1.concat(state2);
-                if new_states_combined.contains(&state) {
-                    Some(state)
-                } else {
-                    None
-                }
+        let new_final_states = new_states
+            .iter()
+            .filter(|(&(state1, state2), _)| {
+                is_final(
+                    state1.is_some_and(|state| self.final_states.contains(&state)),
+                    state2.is_some_and(|state| other.final_states.contains(&state)),
+                )
             })
+            .map(|(_, &state)| state)
             .collect();
-        Self::new(
-            new_states_combined,
-            new_alphabets,
-            new_transition_table
-                .into_iter()
-                .map(|((from, alphabet), to)| {
-                    ((from.0.concat(from.1), alphabet), (to.0.concat(to.1)))
-                })
-                .collect(),
-            self.start_state.concat(other.start_state),
+        let provenance = new_states
+            .iter()
+            .map(|(&(state1, state2), &state)| (state, Provenance::Product(state1, state2)))
+            .collect();
+        Self::new_with_provenance(
+            new_states.into_values().collect(),
+            atoms.into_iter().collect(),
+            new_transition_table,
+            start_state,
             new_final_states,
+            Some(provenance),
         )
     }
 
     pub fn intersection(&self, other: &DFA) -> Result<Self, CustomError> {
-        self.product(
-            other,
-            self.final_states
-                .iter()
-                .copied()
-                .cartesian_product(other.final_states.iter().copied()),
-        )
+        self.product(other, |is_final1, is_final2| is_final1 && is_final2)
     }
 
     pub fn union(&self, other: &DFA) -> Result<Self, CustomError> {
-        self.product(
-            other,
-            self.final_states
-                .iter()
-                .copied()
-                .cartesian_product(other.states.iter().copied())
-                .chain(
-                    self.states
-                        .iter()
-                        .copied()
-                        .cartesian_product(other.final_states.iter().copied()),
-                ),
-        )
+        self.product(other, |is_final1, is_final2| is_final1 || is_final2)
     }
 
     pub fn difference(&self, other: &DFA) -> Result<Self, CustomError> {
-        self.product(
-            other,
-            self.final_states
-                .iter()
-                .copied()
-                .cartesian_product(other.states.difference(&other.final_states).copied()),
-        )
+        self.product(other, |is_final1, is_final2| is_final1 && !is_final2)
+    }
+
+    /// Accepts a string of `self` followed by a string of `other`.
+    ///
+    /// Unlike the product constructions, the concatenation, the Kleene star and the reversal
+    /// go through [`EpsilonNFA`] and back through the subset construction, and the result is
+    /// minimized.
+    ///
+    /// ```
+    /// use re_rs::compile_regex;
+    ///
+    /// let ab = compile_regex("a|b").unwrap();
+    /// let abc = compile_regex("abc").unwrap();
+    /// assert!(ab.concat(&abc).is_equivalent(&compile_regex("(a|b)abc").unwrap()));
+    /// assert!(abc.star().is_equivalent(&compile_regex("(abc)*").unwrap()));
+    /// assert!(abc.reverse().is_equivalent(&compile_regex("cba").unwrap()));
+    ///
+    /// // Strings whose third to last character is an `a`, from the ones whose third character is
+    /// let third = compile_regex("(a|b)(a|b)a(a|b)*").unwrap();
+    /// assert!(third.reverse().is_equivalent(&compile_regex("(a|b)*a(a|b)(a|b)").unwrap()));
+    /// ```
+    pub fn concat(&self, other: &DFA) -> Self {
+        EpsilonNFA::from(self.clone())
+            .concat(&EpsilonNFA::from(other.clone()))
+            .to_dfa()
+            .get_minimized()
+    }
+
+    /// Accepts any number of strings of `self` one after the other, the empty string included.
+    pub fn star(&self) -> Self {
+        EpsilonNFA::from(self.clone())
+            .star()
+            .to_dfa()
+            .get_minimized()
+    }
+
+    /// Accepts the reversed strings of `self`.
+    pub fn reverse(&self) -> Self {
+        EpsilonNFA::from(self.clone())
+            .reverse()
+            .to_dfa()
+            .get_minimized()
+    }
+
+    // Breadth first search over the product of both DFAs, built on the fly from the pair of
+    // start states. `None` is the implicit trap reached on characters without a transition.
+    fn product_reaches(&self, other: &DFA, is_target: impl Fn(bool, bool) -> bool) -> bool {
+        let (atoms, default) = self.get_common_atoms(other);
+        let start_state = (Some(self.start_state), Some(other.start_state));
+        let mut visited = HashSet::from([start_state]);
+        let mut queue = VecDeque::from([start_state]);
+        while let Some((state1, state2)) = queue.pop_front() {
+            if is_target(
+                state1.is_some_and(|state| self.final_states.contains(&state)),
+                state2.is_some_and(|state| other.final_states.contains(&state)),
+            ) {
+                return true;
+            }
+            for alphabet in atoms.iter().map(|atom| atom.get_start()).chain(default) {
+                let next_state = (
+                    state1.and_then(|state| self.get_next_state(state, alphabet)),
+                    state2.and_then(|state| other.get_next_state(state, alphabet)),
+                );
+                if visited.insert(next_state) {
+                    queue.push_back(next_state);
+                }
+            }
+        }
+        false
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let mut visited = HashSet::from([self.start_state]);
+        let mut stack = vec![self.start_state];
+        while let Some(state) = stack.pop() {
+            if self.final_states.contains(&state) {
+                return false;
+            }
+            for &next_state in self.transition_table[&state].values() {
+                if visited.insert(next_state) {
+                    stack.push(next_state);
+                }
+            }
+        }
+        true
+    }
+
+    /// Checks whether both DFAs accept the same language, without building the product.
+    ///
+    /// ```
+    /// use re_rs::compile_regex;
+    ///
+    /// let dfa1 = compile_regex("(a|b)*").unwrap();
+    /// let dfa2 = compile_regex("(a*b*)*").unwrap();
+    /// assert!(dfa1.is_equivalent(&dfa2));
+    ///
+    /// let ab_star_a = compile_regex("ab*a").unwrap();
+    /// let a_or_ab_star_a = compile_regex("a|a(a|b)*a").unwrap();
+    /// assert!(ab_star_a.is_subset_of(&a_or_ab_star_a));
+    /// assert!(!a_or_ab_star_a.is_subset_of(&ab_star_a));
+    /// assert!(!ab_star_a.is_equivalent(&a_or_ab_star_a));
+    ///
+    /// let a_plus = compile_regex("a+").unwrap();
+    /// let b_plus = compile_regex("b+").unwrap();
+    /// assert!(a_plus.is_disjoint(&b_plus));
+    /// assert!(a_plus.intersection(&b_plus).unwrap().is_empty());
+    /// ```
+    pub fn is_equivalent(&self, other: &DFA) -> bool {
+        !self.product_reaches(other, |is_final1, is_final2| is_final1 != is_final2)
+    }
+
+    pub fn is_subset_of(&self, other: &DFA) -> bool {
+        !self.product_reaches(other, |is_final1, is_final2| is_final1 && !is_final2)
+    }
+
+    pub fn is_disjoint(&self, other: &DFA) -> bool {
+        !self.product_reaches(other, |is_final1, is_final2| is_final1 && is_final2)
+    }
+
+    /// Returns the shortest accepted string, the lexicographically smallest among those of the
+    /// same length, or `None` if the language is empty.
+    pub fn get_shortest_accepted_string(&self) -> Option<String> {
+        // The smallest character of every atom, and of the characters outside the alphabet
+        let alphabets = self
+            .get_symbols()
+            .into_iter()
+            .sorted_unstable()
+            .collect_vec();
+        let mut parents: HashMap<State, Option<(State, char)>> =
+            HashMap::from([(self.start_state, None)]);
+        let mut queue = VecDeque::from([self.start_state]);
+        while let Some(state) = queue.pop_front() {
+            if self.final_states.contains(&state) {
+                let mut accepted_string = Vec::new();
+                let mut current_state = state;
+                while let Some((previous_state, alphabet)) = parents[&current_state] {
+                    accepted_string.push(alphabet);
+                    current_state = previous_state;
+                }
+                return Some(accepted_string.into_iter().rev().collect());
+            }
+            for &alphabet in &alphabets {
+                if let Some(next_state) = self.get_next_state(state, alphabet) {
+                    if let Entry::Vacant(entry) = parents.entry(next_state) {
+                        entry.insert(Some((state, alphabet)));
+                        queue.push_back(next_state);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the shortest string accepted by this DFA and rejected by `other`, which exists
+    /// exactly when [`DFA::is_subset_of`] fails.
+    pub fn get_counterexample(&self, other: &DFA) -> Result<Option<String>, CustomError> {
+        Ok(self.difference(other)?.get_shortest_accepted_string())
+    }
+
+    /// Returns the shortest string accepted by exactly one of both DFAs, which exists exactly
+    /// when [`DFA::is_equivalent`] fails.
+    ///
+    /// ```
+    /// use re_rs::compile_regex;
+    ///
+    /// let ab_star_a = compile_regex("ab*a").unwrap();
+    /// let a_or_ab_star_a = compile_regex("a|a(a|b)*a").unwrap();
+    /// let witness = ab_star_a.get_distinguishing_string(&a_or_ab_star_a).unwrap();
+    /// assert_eq!(witness.as_deref(), Some("a"));
+    /// assert_eq!(
+    ///     ab_star_a.get_counterexample(&a_or_ab_star_a).unwrap(),
+    ///     None,
+    /// );
+    ///
+    /// let refactored = compile_regex("a(b|ab)*a").unwrap();
+    /// let witness = ab_star_a.get_distinguishing_string(&refactored).unwrap().unwrap();
+    /// assert_eq!(witness, "aaba");
+    /// assert!(refactored.accepts(&witness) && !ab_star_a.accepts(&witness));
+    /// ```
+    pub fn get_distinguishing_string(&self, other: &DFA) -> Result<Option<String>, CustomError> {
+        Ok([
+            self.get_counterexample(other)?,
+            other.get_counterexample(self)?,
+        ]
+        .into_iter()
+        .flatten()
+        .min_by(|string1, string2| {
+            (string1.chars().count(), string1).cmp(&(string2.chars().count(), string2))
+        }))
     }
 
     pub fn complement(&mut self) {
@@ -617,19 +1966,107 @@ impl DFA {
         dfa_copy
     }
 
-    pub fn find_all<'a>(
-        &self,
-        text: &'a str,
-    ) -> Result<impl Iterator<Item = (Range<usize>, &'a str)>, CustomError> {
-        if text.chars().unique().collect::<HashSet<_>>() != self.alphabets {
-            return Err(CustomError::InvalidAlphabet);
-        }
-        Ok(MatchIterator {
-            dfa: self.get_minimized().into(),
-            text,
-            start: 0,
+    // Walks the range maps, which spares compiling the matcher for a single text
+    pub fn accepts(&self, text: &str) -> bool {
+        let mut current_state = self.start_state;
+        for ch in text.chars() {
+            match self.get_next_state(current_state, ch) {
+                Some(next_state) => current_state = next_state,
+                None => return false,
+            }
         }
-        .map(|range| (range.clone(), &text[range])))
+        self.final_states.contains(&current_state)
+    }
+
+    /// Searches the range maps directly; a [`Regex`] compiles its matcher once instead, which
+    /// is faster per character. A character outside the alphabet without a default edge stops
+    /// the match, as does a trap state.
+    ///
+    /// ```
+    /// use re_rs::compile_regex;
+    ///
+    /// let dfa = compile_regex("ab*a").unwrap();
+    /// let text = "a cab, an abba and a kebab aaa!";
+    /// let found: Vec<(_, &str)> = dfa.find_all(text).unwrap().collect();
+    /// assert_eq!(found, [(10..14, "abba"), (27..29, "aa")]);
+    /// assert!(dfa.accepts("abba") && !dfa.accepts("ab ba"));
+    /// ```
+    pub fn find_all<'d, 'a>(
+        &'d self,
+        text: &'a str,
+    ) -> Result<impl Iterator<Item = (Range<usize>, &'a str)> + use<'d, 'a>, CustomError> {
+        Ok(
+            MatchIterator::new(Searcher::Ranges(self), text, self.match_kind)
+                .map(|range| (range.clone(), &text[range])),
+        )
+    }
+}
+
+impl Search for &DFA {
+    type State = State;
+
+    fn get_start(&mut self) -> Option<State> {
+        (!self.trapped_states.contains(&self.start_state)).then_some(self.start_state)
+    }
+
+    fn is_final(&self, state: &State) -> bool {
+        self.final_states.contains(state)
+    }
+
+    fn step(&mut self, threads: &mut Vec<(State, usize)>, alphabet: char, marks: &mut Marks) {
+        threads.retain_mut(|(state, _)| match self.get_next_state(*state, alphabet) {
+            Some(next_state) if !self.trapped_states.contains(&next_state) => {
+                *state = next_state;
+                marks.insert(next_state.get_index())
+            }
+            _ => false,
+        });
+    }
+}
+
+/// Reads a DFA from a small line based text format:
+///
+/// ```text
+/// # ab*a
+/// states: q0, q1, q2, q3
+/// alphabet: a, b
+/// start: q0
+/// final: q2
+/// q0 a -> q1
+/// q0 b -> q3
+/// q1 a -> q2
+/// q1 b -> q1
+/// q2 a -> q3
+/// q2 b -> q3
+/// q3 a -> q3
+/// q3 b -> q3
+/// ```
+///
+/// `#` starts a comment, and the `states` and `alphabet` headers have to come before the lines
+/// that use them. An alphabet is a single character, quoted as in `' '`, `','` or `'\n'` when it
+/// is whitespace or one of `,:#'`, or a range of characters such as `a-z` or `'\0'-' '`, which
+/// must not overlap. Malformed input is reported as [`CustomError::InvalidFormat`] with the line
+/// and column of the offending token, and the table still has to pass [`DFA::check_validity`].
+///
+/// ```
+/// use re_rs::{CustomError, DFA};
+///
+/// let dfa: DFA = "states: q0, q1\nalphabet: a\nstart: q0\nfinal: q1\nq0 a -> q1\nq1 a -> q1"
+///     .parse()
+///     .unwrap();
+/// assert!(dfa.accepts("aaa"));
+///
+/// let error = "states: q0\nalphabet: a\nstart: q0\nq0 a -> q1".parse::<DFA>();
+/// assert_eq!(error.err(), Some(CustomError::InvalidFormat { line: 4, column: 9 }));
+///
+/// let overlapping = "states: q0\nalphabet: a-c, b\nstart: q0\nq0 a-c -> q0\nq0 b -> q0";
+/// assert_eq!(overlapping.parse::<DFA>().err(), Some(CustomError::InvalidAlphabet));
+/// ```
+impl FromStr for DFA {
+    type Err = CustomError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Definition::parse(text, true)?.into_dfa()
     }
 }
 
@@ -639,13 +2076,7 @@ impl fmt::Display for DFA {
         let states_max_len: usize = self
             .states
             .iter()
-            .map(|state| {
-                state
-                    .get_index()
-                    .iter()
-                    .map(|(_, index)| 1 + index.to_string().len())
-                    .sum()
-            })
+            .map(|state| state.to_string().len())
             .max()
             .unwrap_or_default();
         let separator = "-".repeat(states_max_len.max(4) + states_max_len.max(2) + 3 * buffer + 12);
@@ -656,22 +2087,33 @@ impl fmt::Display for DFA {
             .enumerate()
             .map(|(index, state)| (state, index))
             .collect();
+        // The default edge comes last, as `else`
         let lines = self
             .transition_table
             .iter()
-            .sorted_unstable_by_key(|((from, alphabet), _)| {
+            .flat_map(|(&from, transitions)| {
+                transitions
+                    .get_ranges()
+                    .iter()
+                    .map(move |&(alphabet, to)| (from, Some(alphabet), to))
+                    .chain(transitions.get_default().map(|&to| (from, None, to)))
+            })
+            .sorted_unstable_by_key(|&(from, alphabet, _)| {
                 (
-                    from != &self.start_state,
-                    self.trapped_states.contains(from),
-                    topologically_sorted_states_index[from],
+                    from != self.start_state,
+                    self.trapped_states.contains(&from),
+                    topologically_sorted_states_index[&from],
+                    alphabet.is_none(),
                     alphabet,
                 )
             })
-            .map(|(&(from, alphabet), &to)| {
+            .map(|(from, alphabet, to)| {
                 format!(
                     "|{}|{}|{}|",
                     from.center(states_max_len.max(4) + buffer),
-                    alphabet.center(buffer + 8),
+                    alphabet
+                        .map_or("else".to_string(), |alphabet| alphabet.to_string())
+                        .center(buffer + 8),
                     to.center(states_max_len.max(2) + buffer),
                 )
             })
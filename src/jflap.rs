@@ -0,0 +1,448 @@
+use super::*;
+
+// Import and export of the finite automaton files written by JFLAP:
+//
+//     <structure>
+//         <type>fa</type>
+//         <automaton>
+//             <state id="0" name="q0"><initial/></state>
+//             <state id="1" name="q1"><final/></state>
+//             <transition><from>0</from><to>1</to><read>a</read></transition>
+//             <transition><from>1</from><to>0</to><read/></transition>
+//         </automaton>
+//     </structure>
+//
+// Older versions of JFLAP put the states and transitions directly inside `structure`. A state
+// `id` becomes the index of its `State`, and an empty `read` is an epsilon transition.
+
+// Minimal XML tree, enough for the files JFLAP writes: elements, attributes, text, entity and
+// character references, comments, CDATA sections and the prolog
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+struct XmlReader {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    column: usize,
+}
+
+impl XmlReader {
+    fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            position: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self) -> CustomError {
+        CustomError::InvalidFormat {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        prefix
+            .chars()
+            .enumerate()
+            .all(|(offset, ch)| self.chars.get(self.position + offset) == Some(&ch))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.position += 1;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), CustomError> {
+        if !self.starts_with(expected) {
+            return Err(self.error());
+        }
+        expected.chars().for_each(|_| {
+            self.next();
+        });
+        Ok(())
+    }
+
+    // Consumes everything up to and including `end`
+    fn skip_past(&mut self, end: &str) -> Result<String, CustomError> {
+        let mut skipped = String::new();
+        while !self.starts_with(end) {
+            skipped.push(self.next().ok_or(self.error())?);
+        }
+        self.expect(end)?;
+        Ok(skipped)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    // Skips whitespace, comments, processing instructions and the doctype between elements
+    fn skip_misc(&mut self) -> Result<(), CustomError> {
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.starts_with("<!DOCTYPE") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn parse_document(mut self) -> Result<Element, CustomError> {
+        self.skip_misc()?;
+        let root = self.parse_element()?;
+        self.skip_misc()?;
+        if self.peek().is_some() {
+            return Err(self.error());
+        }
+        Ok(root)
+    }
+
+    fn parse_name(&mut self) -> Result<String, CustomError> {
+        let mut name = String::new();
+        while let Some(ch) = self.peek() {
+            if !(ch.is_alphanumeric() || ['_', '-', '.', ':'].contains(&ch)) {
+                break;
+            }
+            name.push(ch);
+            self.next();
+        }
+        if name.is_empty() {
+            return Err(self.error());
+        }
+        Ok(name)
+    }
+
+    fn parse_element(&mut self) -> Result<Element, CustomError> {
+        let (line, column) = (self.line, self.column);
+        self.expect("<")?;
+        let name = self.parse_name()?;
+        let mut element = Element {
+            name,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: String::new(),
+            line,
+            column,
+        };
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("/>") {
+                self.expect("/>")?;
+                return Ok(element);
+            }
+            if self.starts_with(">") {
+                self.expect(">")?;
+                break;
+            }
+            let key = self.parse_name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = self.next().filter(|&ch| ch == '"' || ch == '\'');
+            let quote = quote.ok_or(self.error())?;
+            let mut value = String::new();
+            loop {
+                match self.peek() {
+                    Some(ch) if ch == quote => break,
+                    Some('&') => value.push(self.parse_reference()?),
+                    Some('<') | None => return Err(self.error()),
+                    Some(_) => value.push(self.next().unwrap()),
+                }
+            }
+            self.next();
+            element.attributes.push((key, value));
+        }
+        loop {
+            if self.starts_with("</") {
+                self.expect("</")?;
+                if self.parse_name()? != element.name {
+                    return Err(self.error());
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if self.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.starts_with("<![CDATA[") {
+                self.expect("<![CDATA[")?;
+                let data = self.skip_past("]]>")?;
+                element.text.push_str(&data);
+            } else if self.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.starts_with("<") {
+                let child = self.parse_element()?;
+                element.children.push(child);
+            } else if self.starts_with("&") {
+                let ch = self.parse_reference()?;
+                element.text.push(ch);
+            } else {
+                let ch = self.next().ok_or(self.error())?;
+                element.text.push(ch);
+            }
+        }
+    }
+
+    fn parse_reference(&mut self) -> Result<char, CustomError> {
+        let error = self.error();
+        self.expect("&")?;
+        let reference = self.skip_past(";")?;
+        let code = match reference.as_str() {
+            "lt" => Some('<' as u32),
+            "gt" => Some('>' as u32),
+            "amp" => Some('&' as u32),
+            "quot" => Some('"' as u32),
+            "apos" => Some('\'' as u32),
+            _ => match reference.strip_prefix('#') {
+                Some(hex) if hex.starts_with('x') => u32::from_str_radix(&hex[1..], 16).ok(),
+                Some(decimal) => decimal.parse().ok(),
+                None => None,
+            },
+        };
+        code.and_then(char::from_u32).ok_or(error)
+    }
+}
+
+impl Element {
+    fn error(&self) -> CustomError {
+        CustomError::InvalidFormat {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn children(&self, name: &'static str) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn child(&self, name: &'static str) -> Result<&Element, CustomError> {
+        self.children(name).exactly_one().map_err(|_| self.error())
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn parse_state(&self) -> Result<State, CustomError> {
+        self.text
+            .trim()
+            .parse::<u32>()
+            .map(|index| State::new(index as usize))
+            .map_err(|_| self.error())
+    }
+}
+
+// With `deterministic` set, empty reads, several initial states and several targets for the
+// same state and alphabet are rejected at the offending element
+pub(crate) fn parse_jff(text: &str, deterministic: bool) -> Result<Definition, CustomError> {
+    let root = XmlReader::new(text).parse_document()?;
+    let kind = root.child("type")?;
+    if root.name != "structure" || kind.text.trim() != "fa" {
+        return Err(kind.error());
+    }
+    let automaton = match root.children("automaton").at_most_one() {
+        Ok(Some(automaton)) => automaton,
+        Ok(None) => &root,
+        Err(mut automata) => return Err(automata.nth(1).unwrap().error()),
+    };
+    let mut definition = Definition {
+        states: Vec::new(),
+        alphabets: Vec::new(),
+        start_states: Vec::new(),
+        final_states: Vec::new(),
+        transitions: Vec::new(),
+        default_transitions: Vec::new(),
+    };
+    for element in automaton.children("state") {
+        let state = element
+            .attribute("id")
+            .and_then(|id| id.trim().parse::<u32>().ok())
+            .map(|index| State::new(index as usize))
+            .ok_or(element.error())?;
+        if definition.states.contains(&state) {
+            return Err(element.error());
+        }
+        definition.states.push(state);
+        if let Some(initial) = element.children("initial").next() {
+            if deterministic && !definition.start_states.is_empty() {
+                return Err(initial.error());
+            }
+            definition.start_states.push(state);
+        }
+        if element.children("final").next().is_some() {
+            definition.final_states.push(state);
+        }
+    }
+    if definition.start_states.is_empty() {
+        return Err(automaton.error());
+    }
+    let mut transition_keys: HashMap<(State, Option<CharRange>), usize> = HashMap::new();
+    for element in automaton.children("transition") {
+        let parse_end = |name: &'static str| {
+            let child = element.child(name)?;
+            let state = child.parse_state()?;
+            if !definition.states.contains(&state) {
+                return Err(child.error());
+            }
+            Ok(state)
+        };
+        let (from, to) = (parse_end("from")?, parse_end("to")?);
+        let alphabet = match element.children("read").at_most_one() {
+            Ok(Some(read)) if read.text.is_empty() => None,
+            Ok(Some(read)) => {
+                let alphabet = read.text.chars().exactly_one().map_err(|_| read.error())?;
+                Some(CharRange::from(alphabet))
+            }
+            Ok(None) => None,
+            Err(_) => return Err(element.error()),
+        };
+        if deterministic && alphabet.is_none() {
+            return Err(element.error());
+        }
+        if !definition.alphabets.contains(&alphabet) {
+            definition.alphabets.push(alphabet);
+        }
+        match transition_keys.entry((from, alphabet)) {
+            Entry::Occupied(entry) => {
+                let to_states = &mut definition.transitions[*entry.get()].2;
+                if !to_states.contains(&to) {
+                    if deterministic {
+                        return Err(element.error());
+                    }
+                    to_states.push(to);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(definition.transitions.len());
+                definition.transitions.push((from, alphabet, vec![to]));
+            }
+        }
+    }
+    Ok(definition)
+}
+
+// The widest range written to JFLAP, as one transition per character
+const MAX_JFF_RANGE_LEN: usize = 256;
+
+// JFLAP only has a single initial state, so several start states are joined by a new state
+// with epsilon transitions to each of them. It has no character ranges either, so a range is
+// written as one transition per character as long as it is no wider than `MAX_JFF_RANGE_LEN`,
+// and a default edge, which stands for most of Unicode, cannot be written at all.
+pub(crate) fn format_jff(mut definition: Definition) -> Result<String, CustomError> {
+    if !definition.default_transitions.is_empty() {
+        return Err(CustomError::UnsupportedJFFDefault);
+    }
+    if let Some(&(_, Some(range), _)) = definition.transitions.iter().find(|(_, alphabet, _)| {
+        // The characters, as the surrogate code points in a range are never written
+        alphabet.is_some_and(|range| range.chars().nth(MAX_JFF_RANGE_LEN).is_some())
+    }) {
+        return Err(CustomError::UnsupportedJFFRange { range });
+    }
+    if definition.start_states.len() > 1 {
+        let start_state = State::new(
+            definition
+                .states
+                .iter()
+                .map(|state| state.get_index() + 1)
+                .max()
+                .unwrap_or_default(),
+        );
+        definition.states.push(start_state);
+        if !definition.alphabets.contains(&None) {
+            definition.alphabets.push(None);
+        }
+        definition.transitions.push((
+            start_state,
+            None,
+            std::mem::take(&mut definition.start_states),
+        ));
+        definition.start_states.push(start_state);
+    }
+    let mut lines = vec![
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>".to_string(),
+        "<structure>".to_string(),
+        "\t<type>fa</type>".to_string(),
+        "\t<automaton>".to_string(),
+    ];
+    // States are spread on a row so that the file opens in a readable state
+    for (index, state) in definition.states.iter().enumerate() {
+        lines.push(format!(
+            "\t\t<state id=\"{}\" name=\"{state}\">",
+            state.get_index()
+        ));
+        lines.push(format!("\t\t\t<x>{}.0</x>", 100 + 150 * index));
+        lines.push("\t\t\t<y>100.0</y>".to_string());
+        if definition.start_states.contains(state) {
+            lines.push("\t\t\t<initial/>".to_string());
+        }
+        if definition.final_states.contains(state) {
+            lines.push("\t\t\t<final/>".to_string());
+        }
+        lines.push("\t\t</state>".to_string());
+    }
+    for (from, alphabet, to_states) in &definition.transitions {
+        let reads = match alphabet {
+            Some(range) => range
+                .chars()
+                .map(|alphabet| format!("\t\t\t<read>{}</read>", escape_xml(alphabet)))
+                .collect_vec(),
+            None => vec!["\t\t\t<read/>".to_string()],
+        };
+        for to in to_states {
+            for read in &reads {
+                lines.push("\t\t<transition>".to_string());
+                lines.push(format!("\t\t\t<from>{}</from>", from.get_index()));
+                lines.push(format!("\t\t\t<to>{}</to>", to.get_index()));
+                lines.push(read.clone());
+                lines.push("\t\t</transition>".to_string());
+            }
+        }
+    }
+    lines.push("\t</automaton>".to_string());
+    lines.push("</structure>".to_string());
+    Ok(lines.join("\n"))
+}
+
+fn escape_xml(alphabet: char) -> String {
+    match alphabet {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&apos;".to_string(),
+        _ if alphabet.is_control() || alphabet.is_whitespace() => {
+            format!("&#{};", alphabet as u32)
+        }
+        _ => alphabet.to_string(),
+    }
+}
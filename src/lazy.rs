@@ -0,0 +1,427 @@
+use super::*;
+
+// Row of a state whose transitions have not been computed yet
+const UNKNOWN_STATE: u32 = u32::MAX;
+// Cached states that are always present: the empty subset and the start subset
+const DEAD_ROW: u32 = 0;
+const START_ROW: u32 = 1;
+// Clears within a single search after which the cache is considered to be thrashing
+const MAX_CLEARS_PER_SEARCH: usize = 3;
+const DEFAULT_CACHE_CAPACITY: usize = 4096;
+
+/// A DFA built lazily from an [`EpsilonNFA`] while searching.
+///
+/// The subset construction of [`EpsilonNFA::to_dfa`] explores every reachable subset up front,
+/// which is exponential for patterns such as `(a|b)*a(a|b)(a|b)...`. A `LazyDFA` only builds
+/// the subsets the searched texts actually visit and caches their transitions. The cache holds
+/// at most `cache_capacity` states and is cleared when it is full; when a single search has to
+/// clear it more than a few times, the rest of that search simulates the NFA directly on sets
+/// of states without caching anything. NFA states that cannot reach a final state are dropped
+/// from every subset, so a search stops as soon as no match can follow.
+///
+/// ```
+/// use re_rs::{parse_regex, LazyDFA};
+///
+/// // The eager DFA of this pattern has more than two million states
+/// let pattern = format!("(a|b)*a{}", "(a|b)".repeat(20));
+/// let nfa = parse_regex(&pattern).unwrap();
+/// let mut dfa = LazyDFA::with_cache_capacity(&nfa, 64);
+///
+/// let text = format!("{}a{}", "ab".repeat(50), "b".repeat(20));
+/// assert!(dfa.accepts(&text));
+/// assert!(!dfa.accepts(&"b".repeat(100)));
+/// assert_eq!(dfa.find(&text), Some(0..text.len()));
+/// assert!(dfa.get_cache_len() <= 64);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LazyDFA {
+    // Dense copy of the NFA, with the states that cannot reach a final state removed
+    epsilon_transitions: Vec<Vec<usize>>,
+    transitions: HashMap<(usize, u32), Vec<usize>>,
+    final_states: Vec<bool>,
+    start_states: Vec<usize>,
+    // Characters with the same transitions from every NFA state share a class, class 0 being
+    // every character outside the alphabet, which follows the default edges. The atoms the
+    // alphabet splits into are sorted.
+    ascii_classes: [u32; 128],
+    atoms: Vec<CharRange>,
+    atom_classes: Vec<u32>,
+    classes_count: usize,
+    cache: Cache,
+    match_kind: MatchKind,
+}
+
+#[derive(Debug, Clone)]
+struct Cache {
+    capacity: usize,
+    rows: HashMap<Vec<usize>, u32>,
+    subsets: Vec<Vec<usize>>,
+    is_final: Vec<bool>,
+    transition_table: Vec<u32>,
+    clears_count: usize,
+    search_clears_count: usize,
+}
+
+// Where a search currently is: a cached state, or a set of NFA states once the cache gave up
+pub(crate) enum Position {
+    Cached(u32),
+    Simulated(Vec<usize>),
+}
+
+impl LazyDFA {
+    pub fn new(nfa: &EpsilonNFA) -> Self {
+        Self::with_cache_capacity(nfa, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Builds a lazy DFA whose cache holds at most `cache_capacity` states, but always room for
+    /// the dead and start states and one transition.
+    pub fn with_cache_capacity(nfa: &EpsilonNFA, cache_capacity: usize) -> Self {
+        let nfa = DenseNFA::from(nfa);
+
+        // States from which a final state can be reached
+        let mut reversed_edges: Vec<Vec<usize>> = vec![Vec::new(); nfa.len()];
+        let edges = nfa
+            .transitions
+            .iter()
+            .enumerate()
+            .flat_map(|(from, transitions)| transitions.values().map(move |to| (from, to)))
+            .chain(nfa.epsilon_transitions.iter().enumerate());
+        for (from, to_states) in edges {
+            for &to in to_states {
+                reversed_edges[to].push(from);
+            }
+        }
+        let mut is_live = vec![false; nfa.len()];
+        let mut stack = (0..nfa.len())
+            .filter(|&state| nfa.final_states[state])
+            .collect_vec();
+        while let Some(state) = stack.pop() {
+            if !is_live[state] {
+                is_live[state] = true;
+                stack.extend(reversed_edges[state].iter().copied());
+            }
+        }
+
+        // The atoms of the alphabet, split at the ends of the ranges of every state
+        let atoms =
+            split_into_atoms(nfa.alphabet_ranges.iter().copied().chain(
+                nfa.transitions.iter().flat_map(|transitions| {
+                    transitions.get_ranges().iter().map(|&(range, _)| range)
+                }),
+            ))
+            .into_iter()
+            .filter(|atom| find_range(&nfa.alphabet_ranges, atom.get_start()).is_some())
+            .collect_vec();
+        // The live targets of every live state on `alphabet`, or through the default edges
+        let get_signature = |alphabet: Option<char>| {
+            nfa.transitions
+                .iter()
+                .enumerate()
+                .filter_map(|(from, transitions)| {
+                    let to_states = match alphabet {
+                        Some(alphabet) => transitions.get(alphabet),
+                        None => transitions.get_default(),
+                    }?
+                    .iter()
+                    .copied()
+                    .filter(|&to| is_live[to])
+                    .collect_vec();
+                    (is_live[from] && !to_states.is_empty()).then_some((from, to_states))
+                })
+                .collect_vec()
+        };
+        // The characters outside the alphabet are in the unknown class, with the default edges
+        let mut signatures: HashMap<Vec<(usize, Vec<usize>)>, u32> =
+            HashMap::from([(get_signature(None), UNKNOWN_CLASS)]);
+        let mut atom_classes = Vec::with_capacity(atoms.len());
+        let mut ascii_classes = [UNKNOWN_CLASS; 128];
+        for &atom in &atoms {
+            let next_class = signatures.len() as u32;
+            let class = *signatures
+                .entry(get_signature(Some(atom.get_start())))
+                .or_insert(next_class);
+            atom_classes.push(class);
+            for ch in atom.chars().take_while(char::is_ascii) {
+                ascii_classes[ch as usize] = class;
+            }
+        }
+        let classes_count = signatures.len();
+
+        let mut transitions: HashMap<(usize, u32), Vec<usize>> = HashMap::new();
+        for (signature, &class) in &signatures {
+            for (from, to_states) in signature {
+                transitions.insert((*from, class), to_states.clone());
+            }
+        }
+        let epsilon_transitions = nfa
+            .epsilon_transitions
+            .iter()
+            .enumerate()
+            .map(|(from, to_states)| {
+                to_states
+                    .iter()
+                    .copied()
+                    .filter(|&to| is_live[from] && is_live[to])
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        let mut dfa = Self {
+            epsilon_transitions,
+            transitions,
+            start_states: nfa
+                .start_states
+                .iter()
+                .copied()
+                .filter(|&state| is_live[state])
+                .collect(),
+            final_states: nfa.final_states,
+            ascii_classes,
+            atoms,
+            atom_classes,
+            classes_count,
+            cache: Cache {
+                capacity: cache_capacity.max(4),
+                rows: HashMap::new(),
+                subsets: Vec::new(),
+                is_final: Vec::new(),
+                transition_table: Vec::new(),
+                clears_count: 0,
+                search_clears_count: 0,
+            },
+            match_kind: MatchKind::default(),
+        };
+        dfa.clear_cache();
+        dfa
+    }
+
+    pub fn get_match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Fails with [`CustomError::UnsupportedMatchKind`] for [`MatchKind::LeftmostFirst`].
+    pub fn set_match_kind(&mut self, match_kind: MatchKind) -> Result<(), CustomError> {
+        self.match_kind = check_match_kind(match_kind)?;
+        Ok(())
+    }
+
+    /// Number of states currently cached, including the dead and start states.
+    pub fn get_cache_len(&self) -> usize {
+        self.cache.subsets.len()
+    }
+
+    /// Number of times the cache was full and had to be cleared.
+    pub fn get_cache_clears_count(&self) -> usize {
+        self.cache.clears_count
+    }
+
+    pub fn accepts(&mut self, text: &str) -> bool {
+        self.cache.search_clears_count = 0;
+        let mut threads = vec![(self.get_start_position(), 0)];
+        let mut marks = Marks::default();
+        for ch in text.chars() {
+            marks.next_step();
+            self.step(&mut threads, ch, &mut marks);
+            if threads.is_empty() {
+                return false;
+            }
+        }
+        self.is_final(&threads[0].0)
+    }
+
+    pub fn find(&mut self, text: &str) -> Option<Range<usize>> {
+        self.find_with_match_kind(text, self.match_kind, &mut Marks::default())
+    }
+
+    pub fn find_iter<'d, 't>(&'d mut self, text: &'t str) -> MatchIterator<'d, 't> {
+        let match_kind = self.match_kind;
+        MatchIterator::new(Searcher::Lazy(self), text, match_kind)
+    }
+
+    // Same search as the compiled matcher, where the dead state plays the trapped states
+    pub(crate) fn find_with_match_kind(
+        &mut self,
+        text: &str,
+        match_kind: MatchKind,
+        marks: &mut Marks,
+    ) -> Option<Range<usize>> {
+        self.cache.search_clears_count = 0;
+        find_leftmost(self, text, match_kind, marks)
+    }
+
+    fn get_class(&self, alphabet: char) -> u32 {
+        if alphabet.is_ascii() {
+            return self.ascii_classes[alphabet as usize];
+        }
+        let index = self.atoms.partition_point(|atom| atom.get_end() < alphabet);
+        match self.atoms.get(index) {
+            Some(atom) if atom.contains(alphabet) => self.atom_classes[index],
+            _ => UNKNOWN_CLASS,
+        }
+    }
+
+    fn get_start_position(&self) -> Position {
+        if self.cache.search_clears_count > MAX_CLEARS_PER_SEARCH {
+            Position::Simulated(self.get_subset(&self.start_states))
+        } else {
+            Position::Cached(START_ROW)
+        }
+    }
+
+    fn is_dead(&self, position: &Position) -> bool {
+        match position {
+            Position::Cached(row) => self.cache.subsets[*row as usize].is_empty(),
+            Position::Simulated(subset) => subset.is_empty(),
+        }
+    }
+
+    fn is_final(&self, position: &Position) -> bool {
+        match position {
+            Position::Cached(row) => self.cache.is_final[*row as usize],
+            Position::Simulated(subset) => subset.iter().any(|&state| self.final_states[state]),
+        }
+    }
+
+    // The cache has room for the new states, as `reserve` made sure before the step
+    fn get_next_position(&mut self, position: Position, class: u32) -> Position {
+        let row = match position {
+            Position::Cached(row) => row,
+            Position::Simulated(subset) => {
+                return Position::Simulated(self.get_next_subset(&subset, class));
+            }
+        };
+        let index = row as usize * self.classes_count + class as usize;
+        if self.cache.transition_table[index] == UNKNOWN_STATE {
+            let subset = self.get_next_subset(&self.cache.subsets[row as usize], class);
+            self.cache.transition_table[index] = self.add_state(subset);
+        }
+        Position::Cached(self.cache.transition_table[index])
+    }
+
+    // Makes room in the cache for a new state per thread, clearing it and moving the threads to
+    // their new rows. Once the search has cleared it too often, or the threads alone would fill
+    // it, the threads switch to simulating the NFA for the rest of the search.
+    fn reserve(&mut self, threads: &mut [(Position, usize)]) {
+        if !matches!(threads.first(), Some((Position::Cached(_), _)))
+            || self.cache.subsets.len() + threads.len() <= self.cache.capacity
+        {
+            return;
+        }
+        let subsets = threads
+            .iter()
+            .map(|(position, _)| match position {
+                Position::Cached(row) => self.cache.subsets[*row as usize].clone(),
+                Position::Simulated(subset) => subset.clone(),
+            })
+            .collect_vec();
+        if self.cache.search_clears_count < MAX_CLEARS_PER_SEARCH
+            && 2 * (threads.len() + 1) <= self.cache.capacity
+        {
+            self.clear_cache();
+            self.cache.clears_count += 1;
+            self.cache.search_clears_count += 1;
+            for ((position, _), subset) in threads.iter_mut().zip(subsets) {
+                *position = Position::Cached(self.add_state(subset));
+            }
+        } else {
+            self.cache.search_clears_count = MAX_CLEARS_PER_SEARCH + 1;
+            for ((position, _), subset) in threads.iter_mut().zip(subsets) {
+                *position = Position::Simulated(subset);
+            }
+        }
+    }
+
+    fn clear_cache(&mut self) {
+        self.cache.rows.clear();
+        self.cache.subsets.clear();
+        self.cache.is_final.clear();
+        self.cache.transition_table.clear();
+        let dead_row = self.add_state(Vec::new());
+        let start_row = self.add_state(self.get_subset(&self.start_states));
+        debug_assert_eq!((dead_row, start_row), (DEAD_ROW, START_ROW));
+    }
+
+    // Adds a new cached state, the start subset may coincide with the dead one
+    fn add_state(&mut self, subset: Vec<usize>) -> u32 {
+        if let Some(&row) = self.cache.rows.get(&subset) {
+            if !(subset.is_empty() && self.cache.subsets.len() == 1) {
+                return row;
+            }
+        }
+        let row = self.cache.subsets.len() as u32;
+        self.cache
+            .transition_table
+            .extend(vec![UNKNOWN_STATE; self.classes_count]);
+        self.cache
+            .is_final
+            .push(subset.iter().any(|&state| self.final_states[state]));
+        self.cache.rows.entry(subset.clone()).or_insert(row);
+        self.cache.subsets.push(subset);
+        row
+    }
+
+    fn get_next_subset(&self, subset: &[usize], class: u32) -> Vec<usize> {
+        let targets = subset
+            .iter()
+            .filter_map(|&state| self.transitions.get(&(state, class)))
+            .flatten()
+            .copied()
+            .collect_vec();
+        self.get_subset(&targets)
+    }
+
+    // Sorted epsilon closure of `states`
+    fn get_subset(&self, states: &[usize]) -> Vec<usize> {
+        let mut visited = HashSet::new();
+        let mut stack = states.to_vec();
+        while let Some(state) = stack.pop() {
+            if visited.insert(state) {
+                stack.extend(self.epsilon_transitions[state].iter().copied());
+            }
+        }
+        visited.into_iter().sorted_unstable().collect()
+    }
+}
+
+impl Search for LazyDFA {
+    type State = Position;
+
+    fn get_start(&mut self) -> Option<Position> {
+        let position = self.get_start_position();
+        (!self.is_dead(&position)).then_some(position)
+    }
+
+    fn is_final(&self, position: &Position) -> bool {
+        self.is_final(position)
+    }
+
+    // The cached threads are told apart by their rows. The simulated ones leave out the NFA
+    // states an earlier thread holds, which keeps a step linear in the size of the NFA.
+    fn step(&mut self, threads: &mut Vec<(Position, usize)>, alphabet: char, marks: &mut Marks) {
+        self.reserve(threads);
+        let class = self.get_class(alphabet);
+        let mut next_threads = Vec::with_capacity(threads.len());
+        for (position, start) in threads.drain(..) {
+            match self.get_next_position(position, class) {
+                Position::Cached(row) => {
+                    if !self.cache.subsets[row as usize].is_empty() && marks.insert(row as usize) {
+                        next_threads.push((Position::Cached(row), start));
+                    }
+                }
+                Position::Simulated(mut subset) => {
+                    subset.retain(|&state| marks.insert(state));
+                    if !subset.is_empty() {
+                        next_threads.push((Position::Simulated(subset), start));
+                    }
+                }
+            }
+        }
+        *threads = next_threads;
+    }
+}
+
+impl From<&EpsilonNFA> for LazyDFA {
+    fn from(value: &EpsilonNFA) -> Self {
+        Self::new(value)
+    }
+}
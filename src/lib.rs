@@ -0,0 +1,58 @@
+//! Regular expressions built on explicit finite automata.
+//!
+//! Patterns are parsed into an [`EpsilonNFA`], determinized into a [`DFA`] and minimized, and
+//! the automata themselves are available for hand construction and for the usual closure
+//! operations.
+//!
+//! ```
+//! use re_rs::Regex;
+//!
+//! let regex = Regex::new("ab*a").unwrap();
+//! let matches: Vec<&str> = regex.find_iter("abbaba").map(|found| found.as_str()).collect();
+//! assert_eq!(matches, ["abba"]);
+//! ```
+
+mod alphabet;
+mod binary;
+mod captures;
+mod constants;
+pub mod dfa;
+mod jflap;
+mod lazy;
+pub mod nfa;
+mod ops;
+mod pike;
+pub mod regex;
+pub mod render;
+#[cfg(feature = "serde")]
+mod serialization;
+mod text;
+pub mod utils;
+
+use alphabet::*;
+use binary::*;
+use captures::*;
+use constants::*;
+use dfa::*;
+use itertools::*;
+use jflap::*;
+use nfa::*;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use text::*;
+use utils::*;
+
+pub use alphabet::{CharRange, RangeMap};
+pub use binary::SerializedDFA;
+pub use captures::{CaptureMatches, Captures};
+pub use dfa::{MatchIterator, MatchKind, DFA};
+pub use lazy::LazyDFA;
+pub use nfa::EpsilonNFA;
+pub use pike::PikeVM;
+pub use regex::{compile_regex, parse_regex, Match, Matches, Regex, RegexBuilder, Split};
+pub use render::{DiagramFormat, Render};
+pub use utils::{CustomError, Provenance, State};
@@ -1,31 +1,13 @@
-#![allow(unused_imports)]
 #![allow(dead_code)]
-// #![allow(unused)]
-
-mod constants;
-mod dfa;
-mod nfa;
-mod regex;
-mod utils;
-
-use constants::*;
-use dfa::*;
-use itertools::*;
-use nfa::*;
-use regex::*;
-use std::collections::VecDeque;
+
+use re_rs::*;
 use std::collections::{HashMap, HashSet};
-use std::fmt;
-use std::hash::Hash;
-use std::ops::Range;
-use std::str::FromStr;
-use utils::*;
 
 fn test1() -> Result<(), CustomError> {
-    let state1 = State::new('q', 0);
-    let state2 = State::new('q', 1);
-    let state3 = State::new('q', 2);
-    let state4 = State::new('q', 3);
+    let state1 = State::new(0);
+    let state2 = State::new(1);
+    let state3 = State::new(2);
+    let state4 = State::new(3);
 
     // ab*a
     let dfa = DFA::new(
@@ -64,11 +46,11 @@ fn test1() -> Result<(), CustomError> {
 }
 
 fn test2() -> Result<(), CustomError> {
-    let state1 = State::new('q', 0);
-    let state2 = State::new('q', 1);
-    let state3 = State::new('q', 2);
-    let state4 = State::new('q', 3);
-    let trapped_state = State::new('q', 4);
+    let state1 = State::new(0);
+    let state2 = State::new(1);
+    let state3 = State::new(2);
+    let state4 = State::new(3);
+    let trapped_state = State::new(4);
 
     // a|ab*a
     let dfa = DFA::new(
@@ -110,11 +92,11 @@ fn test2() -> Result<(), CustomError> {
 }
 
 fn test3() -> Result<(), CustomError> {
-    let state1 = State::new('q', 0);
-    let state2 = State::new('q', 1);
-    let state3 = State::new('q', 2);
-    let state4 = State::new('q', 3);
-    let trapped_state = State::new('q', 4);
+    let state1 = State::new(0);
+    let state2 = State::new(1);
+    let state3 = State::new(2);
+    let state4 = State::new(3);
+    let trapped_state = State::new(4);
 
     // ab*a
     let dfa1 = DFA::new(
@@ -175,11 +157,11 @@ fn test3() -> Result<(), CustomError> {
 }
 
 fn test4() -> Result<(), CustomError> {
-    let state1 = State::new('q', 0);
-    let state2 = State::new('q', 1);
-    let state3 = State::new('q', 2);
-    let state4 = State::new('q', 3);
-    let trapped_state = State::new('q', 4);
+    let state1 = State::new(0);
+    let state2 = State::new(1);
+    let state3 = State::new(2);
+    let state4 = State::new(3);
+    let trapped_state = State::new(4);
 
     // ab*a
     let dfa1 = DFA::new(
@@ -240,10 +222,10 @@ fn test4() -> Result<(), CustomError> {
 }
 
 fn test5() -> Result<(), CustomError> {
-    let state0 = State::new('q', 0);
-    let state1 = State::new('q', 1);
-    let state2 = State::new('q', 2);
-    let state3 = State::new('q', 3);
+    let state0 = State::new(0);
+    let state1 = State::new(1);
+    let state2 = State::new(2);
+    let state3 = State::new(3);
     let nfa = EpsilonNFA::new(
         HashSet::from([state0, state1, state2, state3]),
         HashSet::from([Some('a'), Some('b')]),
@@ -263,10 +245,10 @@ fn test5() -> Result<(), CustomError> {
 }
 
 fn test6() -> Result<(), CustomError> {
-    let state0 = State::new('q', 0);
-    let state1 = State::new('q', 1);
-    let state2 = State::new('q', 2);
-    let state3 = State::new('q', 3);
+    let state0 = State::new(0);
+    let state1 = State::new(1);
+    let state2 = State::new(2);
+    let state3 = State::new(3);
     let mut nfa = EpsilonNFA::new(
         HashSet::from([state0, state1, state2, state3]),
         HashSet::from([Some('a'), Some('b')]),
@@ -3,45 +3,164 @@ use super::*;
 #[derive(Debug, Default, Clone)]
 pub struct EpsilonNFA {
     states: HashSet<State>,
-    alphabets: HashSet<Option<char>>,
-    transition_table: HashMap<(State, Option<char>), HashSet<State>>,
+    alphabets: HashSet<Option<CharRange>>,
+    transition_table: HashMap<State, RangeMap<HashSet<State>>>,
+    epsilon_transitions: HashMap<State, HashSet<State>>,
     start_states: HashSet<State>,
     final_states: HashSet<State>,
 }
 
 impl EpsilonNFA {
-    pub fn new(
+    /// Builds the NFA from `char` or [`CharRange`] labels, `None` being the empty string. Unlike
+    /// the alphabet of a [`DFA`], the ranges may overlap: the targets of the overlapping part
+    /// are joined in the [`RangeMap`] of the state.
+    pub fn new_unoptimized<A: Into<CharRange>>(
         states: HashSet<State>,
-        alphabets: HashSet<Option<char>>,
-        transition_table: HashMap<(State, Option<char>), HashSet<State>>,
+        alphabets: HashSet<Option<A>>,
+        transition_table: HashMap<(State, Option<A>), HashSet<State>>,
         start_states: HashSet<State>,
         final_states: HashSet<State>,
     ) -> Result<Self, CustomError> {
-        let mut nfa = Ok(Self {
+        let mut nfa = Self {
+            states,
+            alphabets: alphabets
+                .into_iter()
+                .map(|alphabet| alphabet.map(Into::into))
+                .collect(),
+            start_states,
+            final_states,
+            ..Self::default()
+        };
+        for ((from, alphabet), to) in transition_table {
+            match alphabet {
+                Some(alphabet) => nfa.add_transitions(from, alphabet.into(), &to),
+                None => nfa.epsilon_transitions.entry(from).or_default().extend(to),
+            }
+        }
+        Ok(nfa)
+    }
+
+    pub fn new<A: Into<CharRange>>(
+        states: HashSet<State>,
+        alphabets: HashSet<Option<A>>,
+        transition_table: HashMap<(State, Option<A>), HashSet<State>>,
+        start_states: HashSet<State>,
+        final_states: HashSet<State>,
+    ) -> Result<Self, CustomError> {
+        let mut nfa = Self::new_unoptimized(
             states,
             alphabets,
             transition_table,
             start_states,
             final_states,
-        })?;
+        )?;
         if AUTO_OPTIMIZE {
             nfa.minimize();
         }
         Ok(nfa)
     }
 
+    pub fn get_states(&self) -> &HashSet<State> {
+        &self.states
+    }
+
+    pub fn get_alphabets(&self) -> &HashSet<Option<CharRange>> {
+        &self.alphabets
+    }
+
+    pub fn get_transition_table(&self) -> &HashMap<State, RangeMap<HashSet<State>>> {
+        &self.transition_table
+    }
+
+    pub fn get_epsilon_transitions(&self) -> &HashMap<State, HashSet<State>> {
+        &self.epsilon_transitions
+    }
+
+    /// Sends every character outside the alphabet from `from` to `to` as well.
+    pub fn add_default_transition(&mut self, from: State, to: State) {
+        let transitions = self.transition_table.entry(from).or_default();
+        let mut targets = transitions.get_default().cloned().unwrap_or_default();
+        targets.insert(to);
+        transitions.set_default(Some(targets));
+    }
+
+    // The characters of the alphabet as sorted, disjoint and non adjacent ranges, default edges
+    // reading every other character
+    pub(crate) fn get_alphabet_ranges(&self) -> Vec<CharRange> {
+        normalize_ranges(self.alphabets.iter().copied().flatten())
+    }
+
+    pub(crate) fn has_default_transitions(&self) -> bool {
+        self.transition_table
+            .values()
+            .any(|transitions| transitions.get_default().is_some())
+    }
+
+    // Adds `to` to the targets of every character of `alphabet`
+    fn add_transitions(&mut self, from: State, alphabet: CharRange, to: &HashSet<State>) {
+        self.transition_table
+            .entry(from)
+            .or_default()
+            .update(alphabet, |targets| {
+                targets.map_or_else(|| to.clone(), |targets| targets | to)
+            });
+    }
+
+    pub fn get_start_states(&self) -> &HashSet<State> {
+        &self.start_states
+    }
+
+    pub fn get_final_states(&self) -> &HashSet<State> {
+        &self.final_states
+    }
+
+    /// Writes the NFA in the text format read by its [`FromStr`] implementation.
+    pub fn to_text(&self) -> String {
+        Definition::from(self).to_string()
+    }
+
+    /// Reads a finite automaton saved by JFLAP (`.jff`), where empty-string transitions become
+    /// epsilon (`None`) transitions.
+    ///
+    /// ```
+    /// use re_rs::EpsilonNFA;
+    ///
+    /// let jff = r#"<structure>
+    ///     <type>fa</type>
+    ///     <state id="0"><initial/></state>
+    ///     <state id="1"/>
+    ///     <state id="2"><final/></state>
+    ///     <transition><from>0</from><to>1</to><read/></transition>
+    ///     <transition><from>1</from><to>2</to><read>&lt;</read></transition>
+    /// </structure>"#;
+    /// let nfa = EpsilonNFA::from_jff(jff).unwrap();
+    /// assert!(nfa.get_alphabets().contains(&None));
+    /// assert!(nfa.to_dfa().accepts("<"));
+    /// assert_eq!(EpsilonNFA::from_jff(&nfa.to_jff().unwrap()).unwrap().to_text(), nfa.to_text());
+    /// ```
+    pub fn from_jff(text: &str) -> Result<Self, CustomError> {
+        parse_jff(text, false)?.into_epsilon_nfa()
+    }
+
+    /// Writes the NFA as a JFLAP finite automaton (`.jff`). JFLAP allows a single initial state,
+    /// so several start states are joined by a new state with epsilon transitions to them. The
+    /// ranges and default edges are refused as for [`DFA::to_jff`].
+    pub fn to_jff(&self) -> Result<String, CustomError> {
+        format_jff(Definition::from(self))
+    }
+
     // Simplified function to get adjacent states from a given state
     fn get_adjacent_states(&self, state: &State) -> Vec<State> {
-        self.transition_table
-            .iter()
-            .filter_map(|((from_state, _), to_state)| {
-                if from_state == state {
-                    Some(to_state)
-                } else {
-                    None
-                }
-            })
-            .flatten()
+        self.epsilon_transitions
+            .get(state)
+            .into_iter()
+            .chain(
+                self.transition_table
+                    .get(state)
+                    .into_iter()
+                    .flat_map(RangeMap::values),
+            )
+            .flat_map(|to_states| to_states.iter().sorted_unstable())
             .copied()
             .unique()
             .collect_vec()
@@ -67,8 +186,15 @@ impl EpsilonNFA {
         let mut visited = HashSet::new();
         let mut stack = Vec::new();
 
-        // Assuming `self.states` is accessible & iterable
-        for &state in &self.states {
+        // Start from the start states and visit the rest in order, so that the result only
+        // depends on the structure of the automaton and not on hashing
+        for state in self
+            .start_states
+            .iter()
+            .sorted_unstable()
+            .chain(self.states.iter().sorted_unstable())
+            .copied()
+        {
             if !visited.contains(&state) {
                 self.dfs(state, &mut visited, &mut stack);
             }
@@ -81,7 +207,7 @@ impl EpsilonNFA {
     fn remove_unreachable_states_with_custom_start_states_and_transition_table(
         &mut self,
         start_states: &HashSet<State>,
-        transition_table: &HashMap<(State, Option<char>), HashSet<State>>,
+        transition_table: &HashMap<State, HashSet<State>>,
     ) {
         let mut reachable_states = HashSet::new();
         let mut stack = Vec::new();
@@ -90,32 +216,56 @@ impl EpsilonNFA {
         }
         while let Some(state) = stack.pop() {
             reachable_states.insert(state);
-            for &alphabet in &self.alphabets {
-                if let Some(next_states) = transition_table.get(&(state, alphabet)) {
-                    for &next_state in next_states {
-                        if !reachable_states.contains(&next_state) {
-                            stack.push(next_state);
-                        }
+            if let Some(next_states) = transition_table.get(&state) {
+                for &next_state in next_states {
+                    if !reachable_states.contains(&next_state) {
+                        stack.push(next_state);
                     }
                 }
             }
         }
+        let retain = |to: &HashSet<State>| {
+            let to: HashSet<State> = to & &reachable_states;
+            (!to.is_empty()).then_some(to)
+        };
         self.states.retain(|state| reachable_states.contains(state));
-        self.transition_table
-            .retain(|(from, _), _| reachable_states.contains(from));
-        self.transition_table
-            .iter_mut()
-            .for_each(|((_, _), to)| to.retain(|state| reachable_states.contains(state)));
+        self.transition_table = self
+            .transition_table
+            .iter()
+            .filter(|(from, _)| reachable_states.contains(from))
+            .map(|(&from, transitions)| (from, transitions.filter_map(retain)))
+            .collect();
+        self.epsilon_transitions = self
+            .epsilon_transitions
+            .iter()
+            .filter(|(from, _)| reachable_states.contains(from))
+            .filter_map(|(&from, to)| Some((from, retain(to)?)))
+            .collect();
         self.start_states
             .retain(|state| reachable_states.contains(state));
         self.final_states
             .retain(|state| reachable_states.contains(state));
     }
 
+    // Every state reached from each state on any character or the empty string
+    fn get_successors(&self) -> HashMap<State, HashSet<State>> {
+        let mut successors: HashMap<State, HashSet<State>> = HashMap::new();
+        for (&from, transitions) in &self.transition_table {
+            successors
+                .entry(from)
+                .or_default()
+                .extend(transitions.values().flatten());
+        }
+        for (&from, to) in &self.epsilon_transitions {
+            successors.entry(from).or_default().extend(to);
+        }
+        successors
+    }
+
     pub fn remove_unreachable_states(&mut self) {
         self.remove_unreachable_states_with_custom_start_states_and_transition_table(
             &self.start_states.clone(),
-            &self.transition_table.clone(),
+            &self.get_successors(),
         );
     }
 
@@ -123,11 +273,9 @@ impl EpsilonNFA {
         self.remove_unreachable_states_with_custom_start_states_and_transition_table(
             &self.final_states.clone(),
             &self
-                .transition_table
-                .iter()
-                .flat_map(|(&(from, alphabet), to)| {
-                    to.iter().map(move |&state| ((state, alphabet), from))
-                })
+                .get_successors()
+                .into_iter()
+                .flat_map(|(from, to)| to.into_iter().map(move |state| (state, from)))
                 .fold(HashMap::new(), |mut hash_map, (key, value)| {
                     hash_map.entry(key).or_insert(HashSet::new()).insert(value);
                     hash_map
@@ -141,13 +289,13 @@ impl EpsilonNFA {
         self.remove_unreachable_states();
     }
 
-    fn epsilon_closure(&self, state: State) -> HashSet<State> {
+    pub(crate) fn epsilon_closure(&self, state: State) -> HashSet<State> {
         let mut closure = HashSet::new();
         let mut stack = vec![state];
 
         while let Some(s) = stack.pop() {
             if closure.insert(s) {
-                if let Some(next_states) = self.transition_table.get(&(s, None)) {
+                if let Some(next_states) = self.epsilon_transitions.get(&s) {
                     for &next_state in next_states {
                         stack.push(next_state);
                     }
@@ -166,25 +314,31 @@ impl EpsilonNFA {
         let mut new_transition_table = HashMap::new();
 
         for &state in &self.states {
-            let closure = self.epsilon_closure(state);
-
-            for &alphabet in &self.alphabets {
-                if let Some(alphabet) = alphabet {
-                    let mut new_states = HashSet::new();
-
-                    for &s in &closure {
-                        if let Some(next_states) = self.transition_table.get(&(s, Some(alphabet))) {
-                            for &next_state in next_states {
-                                new_states.extend(self.epsilon_closure(next_state));
-                            }
-                        }
-                    }
-
-                    if !new_states.is_empty() {
-                        new_transition_table.insert((state, Some(alphabet)), new_states);
-                    }
+            // The transitions of the whole closure, to the closures of their targets
+            let mut transitions = RangeMap::new();
+            for s in self.epsilon_closure(state) {
+                let Some(next_transitions) = self.transition_table.get(&s) else {
+                    continue;
+                };
+                for (alphabet, next_states) in next_transitions.get_ranges() {
+                    transitions.update(*alphabet, |targets: Option<&HashSet<State>>| {
+                        targets.map_or_else(|| next_states.clone(), |targets| targets | next_states)
+                    });
+                }
+                if let Some(next_states) = next_transitions.get_default() {
+                    let targets = transitions.get_default().cloned().unwrap_or_default();
+                    transitions.set_default(Some(&targets | next_states));
                 }
             }
+            let transitions = transitions.map(|next_states| {
+                next_states
+                    .iter()
+                    .flat_map(|&next_state| self.epsilon_closure(next_state))
+                    .collect()
+            });
+            if !transitions.is_empty() {
+                new_transition_table.insert(state, transitions);
+            }
         }
 
         self.start_states = self
@@ -192,12 +346,11 @@ impl EpsilonNFA {
             .iter()
             .flat_map(|&state| self.epsilon_closure(state))
             .collect();
-        self.final_states = self
-            .final_states
-            .iter()
-            .flat_map(|&state| self.epsilon_closure(state))
-            .collect();
+        // The start states and the targets are closed under epsilon transitions, so the final
+        // states stay the same: a state only reachable through epsilon transitions from a final
+        // state is not final itself
         self.transition_table = new_transition_table;
+        self.epsilon_transitions.clear();
         self.alphabets.remove(&None);
         if AUTO_OPTIMIZE {
             self.remove_unreachable_states()
@@ -212,118 +365,499 @@ impl EpsilonNFA {
 
     pub fn to_dfa(&self) -> DFA {
         let self_copy = self.get_non_epsilon_nfa();
-        let mut expand_stack = vec![self_copy
+        // The labels may overlap and the ranges of the states split them further, so the DFA
+        // reads the atoms of the alphabet they all split into
+        let alphabet_ranges = self_copy.get_alphabet_ranges();
+        let dfa_alphabets =
+            split_into_atoms(self_copy.alphabets.iter().copied().flatten().chain(
+                self_copy.transition_table.values().flat_map(|transitions| {
+                    transitions.get_ranges().iter().map(|&(range, _)| range)
+                }),
+            ))
+            .into_iter()
+            .filter(|atom| find_range(&alphabet_ranges, atom.get_start()).is_some())
+            .collect_vec();
+        // The characters outside the alphabet follow the default edges of the whole subset
+        let has_default =
+            self_copy.has_default_transitions() && get_first_outside(&alphabet_ranges).is_some();
+        let get_next_subset = |subset: &[State], alphabet: Option<char>| {
+            subset
+                .iter()
+                .filter_map(|sub_state| self_copy.transition_table.get(sub_state))
+                .filter_map(|transitions| match alphabet {
+                    Some(alphabet) => transitions.get(alphabet),
+                    None => transitions.get_default(),
+                })
+                .flatten()
+                .copied()
+                .unique()
+                .sorted_unstable()
+                .collect_vec()
+        };
+        // Every subset of NFA states is interned into the next dense DFA state ID
+        let start_subset = self_copy
             .start_states
             .iter()
             .copied()
             .sorted_unstable()
-            .collect_vec()];
-        let mut dfa_states = HashSet::new();
-        let dfa_alphabets = self_copy
-            .alphabets
-            .iter()
-            .copied()
-            .filter_map(|alphabet| alphabet)
-            .collect();
+            .collect_vec();
+        let mut dfa_states: HashMap<Vec<State>, State> = HashMap::new();
+        dfa_states.insert(start_subset.clone(), State::new(0));
+        let mut expand_stack = vec![start_subset];
         let mut dfa_transition_table = HashMap::new();
-        let epsilon_state = vec![];
-        dfa_states.insert(epsilon_state.clone());
-        for &alphabet in &dfa_alphabets {
-            dfa_transition_table.insert((epsilon_state.clone(), alphabet), epsilon_state.clone());
+        while let Some(subset) = expand_stack.pop() {
+            let state = dfa_states[&subset];
+            let mut next_states = Vec::new();
+            for alphabet in dfa_alphabets
+                .iter()
+                .map(|alphabet| Some(alphabet.get_start()))
+                .chain(has_default.then_some(None))
+            {
+                let next_subset = get_next_subset(&subset, alphabet);
+                let next_state = match dfa_states.get(&next_subset) {
+                    Some(&next_state) => next_state,
+                    None => {
+                        let next_state = State::new(dfa_states.len());
+                        dfa_states.insert(next_subset.clone(), next_state);
+                        expand_stack.push(next_subset);
+                        next_state
+                    }
+                };
+                next_states.push(next_state);
+            }
+            let default_state = next_states.pop_if(|_| has_default);
+            dfa_transition_table.insert(
+                state,
+                RangeMap::from_sorted(
+                    dfa_alphabets.iter().copied().zip(next_states),
+                    default_state,
+                ),
+            );
         }
-        while let Some(state) = expand_stack.pop() {
-            dfa_states.insert(state.clone());
-            for &alphabet in &dfa_alphabets {
-                let next_state = state
+        let dfa_final_states = dfa_states
+            .iter()
+            .filter(|(subset, _)| {
+                subset
                     .iter()
-                    .copied()
-                    .filter_map(|sub_state| {
-                        self_copy.transition_table.get(&(sub_state, Some(alphabet)))
-                    })
-                    .flatten()
-                    .copied()
-                    .unique()
-                    .sorted_unstable()
-                    .collect_vec();
-                dfa_transition_table.insert((state.clone(), alphabet), next_state.clone());
-                if dfa_states.insert(next_state.clone()) {
-                    expand_stack.push(next_state.clone());
+                    .any(|sub_state| self_copy.final_states.contains(sub_state))
+            })
+            .map(|(_, &state)| state)
+            .collect();
+        let provenance = dfa_states
+            .iter()
+            .map(|(subset, &state)| (state, Provenance::Subset(subset.clone())))
+            .collect();
+        DFA::new_with_provenance(
+            dfa_states.into_values().collect(),
+            dfa_alphabets.into_iter().collect(),
+            dfa_transition_table,
+            State::new(0),
+            dfa_final_states,
+            Some(provenance),
+        )
+        .unwrap()
+    }
+
+    pub fn get_shortest_accepted_string(&self) -> Option<String> {
+        self.to_dfa().get_shortest_accepted_string()
+    }
+
+    /// Returns the shortest string accepted by this NFA and rejected by `other`.
+    pub fn get_counterexample(&self, other: &EpsilonNFA) -> Result<Option<String>, CustomError> {
+        self.to_dfa().get_counterexample(&other.to_dfa())
+    }
+
+    /// Returns the shortest string accepted by exactly one of both NFAs.
+    pub fn get_distinguishing_string(
+        &self,
+        other: &EpsilonNFA,
+    ) -> Result<Option<String>, CustomError> {
+        self.to_dfa().get_distinguishing_string(&other.to_dfa())
+    }
+
+    /// Accepts a string of `self` followed by a string of `other`.
+    ///
+    /// The combinators renumber the states of their operands from zero so that they never
+    /// collide, and join the pieces with epsilon transitions as in the Thompson construction.
+    ///
+    /// ```
+    /// use re_rs::{compile_regex, parse_regex, EpsilonNFA};
+    ///
+    /// let ab = parse_regex("a|b").unwrap();
+    /// let c = parse_regex("c").unwrap();
+    /// let nfa = ab.concat(&c).union(&c.star());
+    /// assert!(nfa.to_dfa().is_equivalent(&compile_regex("(a|b)c|c*").unwrap()));
+    ///
+    /// // `a*b` as a DFA, whose start state loops on `a`
+    /// let nfa = EpsilonNFA::from(compile_regex("a*b").unwrap()).plus();
+    /// assert!(nfa.to_dfa().is_equivalent(&compile_regex("(a*b)+").unwrap()));
+    /// assert!(!nfa.to_dfa().accepts("aba"));
+    /// ```
+    pub fn concat(&self, other: &Self) -> Self {
+        let (mut nfa, first, second) = Self::disjoint_union(self, other);
+        for &from in &first.final_states {
+            for &to in &second.start_states {
+                nfa.add_epsilon_transition(from, to);
+            }
+        }
+        nfa.start_states = first.start_states;
+        nfa.final_states = second.final_states;
+        nfa
+    }
+
+    /// Accepts the strings of either `self` or `other`, from a new start state.
+    pub fn union(&self, other: &Self) -> Self {
+        let (mut nfa, first, second) = Self::disjoint_union(self, other);
+        let start = nfa.add_new_state();
+        for &to in first.start_states.iter().chain(&second.start_states) {
+            nfa.add_epsilon_transition(start, to);
+        }
+        nfa.start_states = HashSet::from([start]);
+        nfa.final_states = &first.final_states | &second.final_states;
+        nfa
+    }
+
+    /// Accepts any number of strings of `self` one after the other, the empty string included.
+    pub fn star(&self) -> Self {
+        self.plus().optional()
+    }
+
+    /// Accepts one or more strings of `self` one after the other.
+    pub fn plus(&self) -> Self {
+        let mut nfa = self.renumbered(0);
+        for from in nfa.final_states.clone() {
+            for to in nfa.start_states.clone() {
+                nfa.add_epsilon_transition(from, to);
+            }
+        }
+        nfa
+    }
+
+    /// Accepts the strings of `self` and the empty string, from a new start state that is
+    /// also final.
+    pub fn optional(&self) -> Self {
+        let mut nfa = self.renumbered(0);
+        let start = nfa.add_new_state();
+        for to in nfa.start_states.clone() {
+            nfa.add_epsilon_transition(start, to);
+        }
+        nfa.start_states = HashSet::from([start]);
+        nfa.final_states.insert(start);
+        nfa
+    }
+
+    /// Accepts between `min` and `max` strings of `self` one after the other, without an upper
+    /// bound when `max` is `None`.
+    ///
+    /// ```
+    /// use re_rs::{compile_regex, parse_regex};
+    ///
+    /// let ab = parse_regex("ab").unwrap();
+    /// let nfa = ab.repeat(2, Some(3));
+    /// assert!(nfa.to_dfa().is_equivalent(&compile_regex("abab(ab)?").unwrap()));
+    /// let nfa = ab.repeat(1, None);
+    /// assert!(nfa.to_dfa().is_equivalent(&compile_regex("(ab)+").unwrap()));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is less than `min`.
+    pub fn repeat(&self, min: usize, max: Option<usize>) -> Self {
+        assert!(
+            max.is_none_or(|max| min <= max),
+            "The maximum repetition count is less than the minimum"
+        );
+        let mut nfa = Self::empty_string();
+        for _ in 0..min {
+            nfa = nfa.concat(self);
+        }
+        match max {
+            Some(max) => {
+                let optional = self.optional();
+                for _ in min..max {
+                    nfa = nfa.concat(&optional);
+                }
+                nfa
+            }
+            None => nfa.concat(&self.star()),
+        }
+    }
+
+    /// Accepts the reversed strings of `self`, by reversing every transition and swapping the
+    /// start and final states. Reversed default edges still read the characters outside the
+    /// alphabet.
+    pub fn reverse(&self) -> Self {
+        let mut nfa = Self {
+            states: self.states.clone(),
+            alphabets: self.alphabets.clone(),
+            start_states: self.final_states.clone(),
+            final_states: self.start_states.clone(),
+            ..Self::default()
+        };
+        for (&from, transitions) in &self.transition_table {
+            for (alphabet, to_states) in transitions.get_ranges() {
+                for &to in to_states {
+                    nfa.add_transitions(to, *alphabet, &HashSet::from([from]));
                 }
             }
+            for &to in transitions.get_default().into_iter().flatten() {
+                nfa.add_default_transition(to, from);
+            }
         }
-        let dfa_states_combined = dfa_states
+        for (&from, to_states) in &self.epsilon_transitions {
+            for &to in to_states {
+                nfa.add_epsilon_transition(to, from);
+            }
+        }
+        nfa
+    }
+
+    // Accepts only the empty string
+    fn empty_string() -> Self {
+        let state = State::new(0);
+        Self {
+            states: HashSet::from([state]),
+            start_states: HashSet::from([state]),
+            final_states: HashSet::from([state]),
+            ..Self::default()
+        }
+    }
+
+    // Copy with every state mentioned anywhere renumbered densely from `offset`, in order
+    fn renumbered(&self, offset: usize) -> Self {
+        let mapping: HashMap<State, State> = self
+            .states
             .iter()
-            .map(|v| {
-                v.into_iter()
-                    .fold(State::new_empty(), |acc, &state| acc.concat(state))
-            })
-            .collect();
-        let dfa_final_states = dfa_states
-            .into_iter()
-            .filter(|state| {
-                state
+            .chain(&self.start_states)
+            .chain(&self.final_states)
+            .chain(
+                self.transition_table
                     .iter()
-                    .copied()
-                    .collect::<HashSet<_>>()
-                    .intersection(&self_copy.final_states)
-                    .next()
-                    .is_some()
-            })
-            .map(|v| {
-                v.into_iter()
-                    .fold(State::new_empty(), |acc, state| acc.concat(state))
-            })
+                    .flat_map(|(from, transitions)| transitions.values().flatten().chain([from])),
+            )
+            .chain(
+                self.epsilon_transitions
+                    .iter()
+                    .flat_map(|(from, to)| to.iter().chain([from])),
+            )
+            .copied()
+            .sorted_unstable()
+            .dedup()
+            .enumerate()
+            .map(|(index, state)| (state, State::new(offset + index)))
             .collect();
-        let mut dfa = DFA::new(
-            dfa_states_combined,
-            dfa_alphabets,
-            dfa_transition_table
-                .into_iter()
-                .map(|((from, alphabet), to)| {
-                    (
-                        (
-                            from.into_iter()
-                                .fold(State::new_empty(), |acc, state| acc.concat(state)),
-                            alphabet,
-                        ),
-                        (to.into_iter()
-                            .fold(State::new_empty(), |acc, state| acc.concat(state))),
-                    )
-                })
+        let rename = |states: &HashSet<State>| states.iter().map(|state| mapping[state]).collect();
+        Self {
+            states: mapping.values().copied().collect(),
+            alphabets: self.alphabets.clone(),
+            transition_table: self
+                .transition_table
+                .iter()
+                .map(|(from, transitions)| (mapping[from], transitions.map(rename)))
                 .collect(),
-            self_copy
-                .start_states
+            epsilon_transitions: self
+                .epsilon_transitions
                 .iter()
-                .sorted_unstable()
-                .fold(State::new_empty(), |acc, &state| acc.concat(state)),
-            dfa_final_states,
+                .map(|(from, to)| (mapping[from], rename(to)))
+                .collect(),
+            start_states: rename(&self.start_states),
+            final_states: rename(&self.final_states),
+        }
+    }
+
+    // Renumbers both NFAs into disjoint ranges and merges their states and transitions over the
+    // union of their alphabets. The start and final states of the merged NFA are left to the
+    // caller.
+    fn disjoint_union(first: &Self, second: &Self) -> (Self, Self, Self) {
+        let mut first = first.renumbered(0);
+        let mut second = second.renumbered(first.states.len());
+        first.extend_alphabet(&second.alphabets);
+        second.extend_alphabet(&first.alphabets);
+        let nfa = Self {
+            states: &first.states | &second.states,
+            alphabets: first.alphabets.clone(),
+            transition_table: first
+                .transition_table
+                .iter()
+                .chain(&second.transition_table)
+                .map(|(&from, transitions)| (from, transitions.clone()))
+                .collect(),
+            epsilon_transitions: first
+                .epsilon_transitions
+                .iter()
+                .chain(&second.epsilon_transitions)
+                .map(|(&from, to)| (from, to.clone()))
+                .collect(),
+            ..Self::default()
+        };
+        (nfa, first, second)
+    }
+
+    // Adds `alphabets` to the alphabet. The default edges no longer read the new characters, so
+    // they get explicit transitions on them instead.
+    fn extend_alphabet(&mut self, alphabets: &HashSet<Option<CharRange>>) {
+        let old_ranges = self.get_alphabet_ranges();
+        self.alphabets.extend(alphabets);
+        let added_ranges = split_into_atoms(
+            self.get_alphabet_ranges()
+                .into_iter()
+                .chain(old_ranges.clone()),
         )
-        .unwrap();
-        if AUTO_OPTIMIZE {
-            dfa.minimize();
+        .into_iter()
+        .filter(|atom| find_range(&old_ranges, atom.get_start()).is_none())
+        .collect_vec();
+        for transitions in self.transition_table.values_mut() {
+            let Some(targets) = transitions.get_default().cloned() else {
+                continue;
+            };
+            for &range in &added_ranges {
+                transitions.update(range, |to| {
+                    to.map_or_else(|| targets.clone(), |to| to | &targets)
+                });
+            }
+        }
+    }
+
+    // The states are dense after renumbering, so the next index is free
+    fn add_new_state(&mut self) -> State {
+        let state = State::new(self.states.len());
+        self.states.insert(state);
+        state
+    }
+
+    fn add_epsilon_transition(&mut self, from: State, to: State) {
+        self.alphabets.insert(None);
+        self.epsilon_transitions.entry(from).or_default().insert(to);
+    }
+}
+
+// The states of an NFA numbered from 0 in sorted order, for the engines that simulate it: every
+// state the NFA mentions anywhere, with its transitions and epsilon transitions by number
+#[derive(Debug, Clone)]
+pub(crate) struct DenseNFA {
+    pub transitions: Vec<RangeMap<Vec<usize>>>,
+    pub epsilon_transitions: Vec<Vec<usize>>,
+    pub final_states: Vec<bool>,
+    pub start_states: Vec<usize>,
+    // The alphabet as sorted ranges, the default edges reading every other character
+    pub alphabet_ranges: Vec<CharRange>,
+}
+
+impl DenseNFA {
+    pub fn len(&self) -> usize {
+        self.final_states.len()
+    }
+
+    // The sorted states reachable from `states` through epsilon transitions, `states` included
+    pub fn epsilon_closure(&self, states: &[usize]) -> Vec<usize> {
+        let mut closure = HashSet::new();
+        let mut stack = states.to_vec();
+        while let Some(state) = stack.pop() {
+            if closure.insert(state) {
+                stack.extend(&self.epsilon_transitions[state]);
+            }
+        }
+        closure.into_iter().sorted_unstable().collect()
+    }
+}
+
+impl From<&EpsilonNFA> for DenseNFA {
+    fn from(value: &EpsilonNFA) -> Self {
+        let states = value
+            .states
+            .iter()
+            .chain(&value.start_states)
+            .chain(&value.final_states)
+            .chain(
+                value
+                    .transition_table
+                    .values()
+                    .flat_map(RangeMap::values)
+                    .chain(value.epsilon_transitions.values())
+                    .flatten(),
+            )
+            .copied()
+            .sorted_unstable()
+            .dedup()
+            .collect_vec();
+        let index: HashMap<State, usize> = states
+            .iter()
+            .enumerate()
+            .map(|(index, &state)| (state, index))
+            .collect();
+        let get_indices = |to_states: &HashSet<State>| {
+            to_states
+                .iter()
+                .map(|to| index[to])
+                .sorted_unstable()
+                .collect_vec()
+        };
+        let mut transitions = vec![RangeMap::new(); states.len()];
+        for (from, to_states) in &value.transition_table {
+            transitions[index[from]] = to_states.map(get_indices);
+        }
+        let mut epsilon_transitions = vec![Vec::new(); states.len()];
+        for (from, to_states) in &value.epsilon_transitions {
+            epsilon_transitions[index[from]] = get_indices(to_states);
+        }
+        Self {
+            transitions,
+            epsilon_transitions,
+            final_states: states
+                .iter()
+                .map(|state| value.final_states.contains(state))
+                .collect(),
+            start_states: get_indices(&value.start_states),
+            alphabet_ranges: value.get_alphabet_ranges(),
         }
-        dfa
     }
 }
 
 impl From<DFA> for EpsilonNFA {
     fn from(value: DFA) -> Self {
-        Self::new(
-            value.get_states().clone(),
-            value
+        Self {
+            states: value.get_states().clone(),
+            alphabets: value
                 .get_alphabets()
                 .iter()
                 .map(|&alphabet| Some(alphabet))
                 .collect(),
-            value
+            transition_table: value
                 .get_transition_table()
                 .iter()
-                .map(|(&(from, alphabet), &to)| ((from, Some(alphabet)), HashSet::from([to])))
+                .map(|(&from, transitions)| (from, transitions.map(|&to| HashSet::from([to]))))
                 .collect(),
-            HashSet::from([*value.get_start_state()]),
-            value.get_final_states().clone(),
-        )
-        .unwrap()
+            start_states: HashSet::from([*value.get_start_state()]),
+            final_states: value.get_final_states().clone(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Reads an epsilon NFA from the text format described on [`DFA`]'s [`FromStr`]
+/// implementation, where `start` may list several states, a bare `ε` is an epsilon transition
+/// and a transition may have several targets:
+///
+/// ```
+/// use re_rs::EpsilonNFA;
+///
+/// let text = "\
+/// states: q0, q1, q2
+/// alphabet: ε, a, b
+/// start: q0
+/// final: q2
+/// q0 ε -> q1
+/// q0 a -> q0, q1
+/// q1 b -> q2";
+/// let nfa: EpsilonNFA = text.parse().unwrap();
+/// assert!(nfa.to_dfa().accepts("aab"));
+/// assert_eq!(nfa.to_text(), text);
+/// ```
+impl FromStr for EpsilonNFA {
+    type Err = CustomError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Definition::parse(text, false)?.into_epsilon_nfa()
     }
 }
 
@@ -333,13 +867,7 @@ impl fmt::Display for EpsilonNFA {
         let states_max_len: usize = self
             .states
             .iter()
-            .map(|state| {
-                state
-                    .get_index()
-                    .iter()
-                    .map(|(_, index)| 1 + index.to_string().len())
-                    .sum()
-            })
+            .map(|state| state.to_string().len())
             .max()
             .unwrap_or_default();
         let separator = "-".repeat(states_max_len.max(4) + states_max_len.max(2) + 3 * buffer + 12);
@@ -350,21 +878,39 @@ impl fmt::Display for EpsilonNFA {
             .enumerate()
             .map(|(index, state)| (state, index))
             .collect();
+        // Epsilon transitions come first as `ε`, and the default edge last as `else`
         let lines = self
-            .transition_table
+            .epsilon_transitions
             .iter()
-            .sorted_unstable_by_key(|((from, alphabet), _)| {
+            .map(|(&from, to)| (from, "ε".to_string(), to))
+            .chain(
+                self.transition_table
+                    .iter()
+                    .flat_map(|(&from, transitions)| {
+                        transitions
+                            .get_ranges()
+                            .iter()
+                            .map(move |(alphabet, to)| (from, alphabet.to_string(), to))
+                            .chain(
+                                transitions
+                                    .get_default()
+                                    .map(|to| (from, "else".to_string(), to)),
+                            )
+                    }),
+            )
+            .enumerate()
+            .sorted_unstable_by_key(|(index, (from, _, _))| {
                 (
                     self.start_states.contains(from),
                     topologically_sorted_states_index[from],
-                    alphabet,
+                    *index,
                 )
             })
-            .map(|(&(from, alphabet), to)| {
+            .map(|(_, (from, alphabet, to))| {
                 format!(
                     "|{}|{}|{}|",
                     from.center(states_max_len.max(4) + buffer),
-                    alphabet.unwrap_or('ε').center(buffer + 8),
+                    alphabet.center(buffer + 8),
                     format!(
                         "{{{}}}",
                         to.iter()
@@ -398,7 +944,7 @@ impl fmt::Display for EpsilonNFA {
         write!(
             f, "States: {{{}}}\nAlphabets: {{{}}}\nStart States: {{{}}}\nFinal States: {{{}}}\n\nTransition Table:\n\n{}",
             self.states.iter().sorted_unstable().map(|state| state.to_string()).join(", "),
-            self.alphabets.iter().sorted_unstable().map(|alphabet| alphabet.unwrap_or('ε')).join(", "),
+            self.alphabets.iter().sorted_unstable().map(|alphabet| alphabet.map_or("ε".to_string(), |alphabet| alphabet.to_string())).join(", "),
             self.start_states.iter().sorted_unstable().map(|state| state.to_string()).join(", "),
             self.final_states.iter().sorted_unstable().map(|state| state.to_string()).join(", "),
             transition_table,
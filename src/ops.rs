@@ -0,0 +1,150 @@
+use super::*;
+use std::ops::{Add, BitAnd, BitOr, Mul, Not, Sub};
+
+// Operator overloading for the closure operations: `|` union, `&` intersection, `-` difference,
+// `!` complement and `+` or `*` concatenation. Every binary operator takes its operands by value
+// or by reference on either side, and returns the type of its operands unless told otherwise.
+macro_rules! impl_binary_operator {
+    ($type:ty, $trait:ident, $method:ident, $operation:expr) => {
+        impl_binary_operator! { $type => $type, $trait, $method, $operation }
+    };
+    ($type:ty => $output:ty, $trait:ident, $method:ident, $operation:expr) => {
+        impl $trait<&$type> for &$type {
+            type Output = $output;
+
+            fn $method(self, other: &$type) -> $output {
+                let operation: fn(&$type, &$type) -> $output = $operation;
+                operation(self, other)
+            }
+        }
+
+        impl $trait<$type> for &$type {
+            type Output = $output;
+
+            fn $method(self, other: $type) -> $output {
+                self.$method(&other)
+            }
+        }
+
+        impl $trait<&$type> for $type {
+            type Output = $output;
+
+            fn $method(self, other: &$type) -> $output {
+                (&self).$method(other)
+            }
+        }
+
+        impl $trait<$type> for $type {
+            type Output = $output;
+
+            fn $method(self, other: $type) -> $output {
+                (&self).$method(&other)
+            }
+        }
+    };
+}
+
+// The product constructions only fail on an invalid table, and they always build a total one
+impl_binary_operator! { DFA, BitOr, bitor, |a, b| a.union(b).unwrap() }
+impl_binary_operator! { DFA, BitAnd, bitand, |a, b| a.intersection(b).unwrap() }
+impl_binary_operator! { DFA, Sub, sub, |a, b| a.difference(b).unwrap() }
+impl_binary_operator! { DFA, Add, add, DFA::concat }
+impl_binary_operator! { DFA, Mul, mul, DFA::concat }
+
+/// The complement, relative to the alphabet of the DFA.
+///
+/// ```
+/// use re_rs::compile_regex;
+///
+/// let [a, b, c] = ["a(a|b)*", "(a|b)*b", "ab"].map(|pattern| compile_regex(pattern).unwrap());
+/// let dfa = (&a | &b) - &c;
+/// assert!(dfa.accepts("aab") && dfa.accepts("b") && !dfa.accepts("ab"));
+/// assert!((&a & &b).is_equivalent(&compile_regex("a(a|b)*b").unwrap()));
+/// assert!((!&a).is_equivalent(&compile_regex("(b(a|b)*)?").unwrap()));
+/// assert!((&c * &c.star()).is_equivalent(&compile_regex("(ab)+").unwrap()));
+/// ```
+impl Not for &DFA {
+    type Output = DFA;
+
+    fn not(self) -> DFA {
+        self.get_complement()
+    }
+}
+
+impl Not for DFA {
+    type Output = DFA;
+
+    fn not(mut self) -> DFA {
+        self.complement();
+        self
+    }
+}
+
+// Intersection, difference and complement have no Thompson construction, so they go through
+// the DFA
+impl_binary_operator! { EpsilonNFA, BitOr, bitor, EpsilonNFA::union }
+impl_binary_operator! { EpsilonNFA, BitAnd, bitand, |a, b| (a.to_dfa() & b.to_dfa()).into() }
+impl_binary_operator! { EpsilonNFA, Sub, sub, |a, b| (a.to_dfa() - b.to_dfa()).into() }
+impl_binary_operator! { EpsilonNFA, Add, add, EpsilonNFA::concat }
+impl_binary_operator! { EpsilonNFA, Mul, mul, EpsilonNFA::concat }
+
+/// The complement, relative to the alphabet of the NFA without epsilon.
+///
+/// ```
+/// use re_rs::{compile_regex, parse_regex};
+///
+/// let [a, b] = ["a|b", "b|c"].map(|pattern| parse_regex(pattern).unwrap());
+/// let nfa = (&a + &b).star() - (&a & &b);
+/// assert!(nfa.to_dfa().is_equivalent(&compile_regex("((a|b)(b|c))*").unwrap()));
+/// assert!(!(!&a).to_dfa().accepts("a") && (!a).to_dfa().accepts("ab"));
+/// ```
+impl Not for &EpsilonNFA {
+    type Output = EpsilonNFA;
+
+    fn not(self) -> EpsilonNFA {
+        EpsilonNFA::from(!self.to_dfa())
+    }
+}
+
+impl Not for EpsilonNFA {
+    type Output = EpsilonNFA;
+
+    fn not(self) -> EpsilonNFA {
+        !&self
+    }
+}
+
+// A regex has no pattern for the result, so the operators work on the compiled DFAs
+impl_binary_operator! { Regex => DFA, BitOr, bitor, |a, b| a.get_dfa() | b.get_dfa() }
+impl_binary_operator! { Regex => DFA, BitAnd, bitand, |a, b| a.get_dfa() & b.get_dfa() }
+impl_binary_operator! { Regex => DFA, Sub, sub, |a, b| a.get_dfa() - b.get_dfa() }
+impl_binary_operator! { Regex => DFA, Add, add, |a, b| a.get_dfa() + b.get_dfa() }
+impl_binary_operator! { Regex => DFA, Mul, mul, |a, b| a.get_dfa() * b.get_dfa() }
+
+/// The complement of the compiled DFA, relative to its alphabet.
+///
+/// ```
+/// use re_rs::{compile_regex, Regex};
+///
+/// let [a, b, c] = ["a(a|b)*", "(a|b)*b", "ab"].map(|pattern| Regex::new(pattern).unwrap());
+/// let dfa = (&a | &b) - c.get_dfa();
+/// assert!(dfa.accepts("aab") && dfa.accepts("b") && !dfa.accepts("ab"));
+/// assert!((&a & &b).is_equivalent(&compile_regex("a(a|b)*b").unwrap()));
+/// assert!((!&a).is_equivalent(&compile_regex("(b(a|b)*)?").unwrap()));
+/// assert!((&c + &c).is_equivalent(&compile_regex("abab").unwrap()));
+/// ```
+impl Not for &Regex {
+    type Output = DFA;
+
+    fn not(self) -> DFA {
+        !self.get_dfa()
+    }
+}
+
+impl Not for Regex {
+    type Output = DFA;
+
+    fn not(self) -> DFA {
+        !&self
+    }
+}
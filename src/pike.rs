@@ -0,0 +1,156 @@
+use super::*;
+
+/// A matcher that simulates an [`EpsilonNFA`] directly, Pike VM style.
+///
+/// The search keeps the set of active NFA states, each tagged with the position its match
+/// started at, and advances all of them together one character at a time. No DFA is built,
+/// eagerly or lazily, so memory stays bounded by the NFA and its precomputed epsilon closures
+/// whatever the pattern and the text, and a search visits each state at most once per
+/// character: `O(n · m)` for a text of length `n` and an NFA with `m` states and closure
+/// entries. It is slower than [`DFA`] and [`LazyDFA`] per character, in exchange for those
+/// guarantees, and reports the same matches for the same [`MatchKind`].
+///
+/// ```
+/// use re_rs::{parse_regex, MatchKind, PikeVM, Regex};
+///
+/// let pattern = format!("(a|b)*a{}", "(a|b)".repeat(20));
+/// let vm = PikeVM::new(&parse_regex(&pattern).unwrap());
+/// let text = format!("{}a{}", "ab".repeat(50), "b".repeat(20));
+/// assert!(vm.accepts(&text));
+/// assert_eq!(vm.find(&text), Some(0..text.len()));
+///
+/// let vm = PikeVM::new(&parse_regex("ab*a").unwrap());
+/// let regex = Regex::new("ab*a").unwrap();
+/// let text = "abbaxaaba";
+/// assert_eq!(
+///     vm.find_iter(text).collect::<Vec<_>>(),
+///     regex.find_iter(text).map(|found| found.range()).collect::<Vec<_>>()
+/// );
+///
+/// let mut vm = PikeVM::new(&parse_regex("ab*").unwrap());
+/// assert_eq!(vm.find("abbb"), Some(0..4));
+/// vm.set_match_kind(MatchKind::Shortest).unwrap();
+/// assert_eq!(vm.find_iter("abab").collect::<Vec<_>>(), [0..1, 2..3]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PikeVM {
+    // Dense copy of the NFA: the labelled transitions and the epsilon closure of every state,
+    // the default edges reading the characters outside the alphabet ranges
+    transitions: Vec<RangeMap<Vec<usize>>>,
+    alphabet_ranges: Vec<CharRange>,
+    epsilon_closures: Vec<Vec<usize>>,
+    final_states: Vec<bool>,
+    start_closure: Vec<usize>,
+    match_kind: MatchKind,
+}
+
+impl PikeVM {
+    pub fn new(nfa: &EpsilonNFA) -> Self {
+        let nfa = DenseNFA::from(nfa);
+        let epsilon_closures = (0..nfa.len())
+            .map(|state| nfa.epsilon_closure(&[state]))
+            .collect();
+        Self {
+            start_closure: nfa.epsilon_closure(&nfa.start_states),
+            epsilon_closures,
+            transitions: nfa.transitions,
+            alphabet_ranges: nfa.alphabet_ranges,
+            final_states: nfa.final_states,
+            match_kind: MatchKind::default(),
+        }
+    }
+
+    pub fn get_match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Fails with [`CustomError::UnsupportedMatchKind`] for [`MatchKind::LeftmostFirst`], as
+    /// the NFA keeps no order of the alternatives.
+    pub fn set_match_kind(&mut self, match_kind: MatchKind) -> Result<(), CustomError> {
+        self.match_kind = check_match_kind(match_kind)?;
+        Ok(())
+    }
+
+    pub fn accepts(&self, text: &str) -> bool {
+        let mut marks = Marks::default();
+        let mut states = self.start_closure.clone();
+        for ch in text.chars() {
+            marks.next_step();
+            let mut next_states = Vec::new();
+            for state in states {
+                self.add_targets(state, ch, &mut marks, |to| next_states.push(to));
+            }
+            if next_states.is_empty() {
+                return false;
+            }
+            states = next_states;
+        }
+        states.iter().any(|&state| self.final_states[state])
+    }
+
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        find_leftmost(&mut &*self, text, self.match_kind, &mut Marks::default())
+    }
+
+    pub fn find_iter<'d, 't>(&'d self, text: &'t str) -> MatchIterator<'d, 't> {
+        MatchIterator::new(Searcher::Pike(self), text, self.match_kind)
+    }
+
+    // Follows the transitions of `state` on `alphabet` and their epsilon closures, skipping the
+    // states already marked in this step. A marked state had its whole closure added with it.
+    fn add_targets(
+        &self,
+        state: usize,
+        alphabet: char,
+        marks: &mut Marks,
+        mut add: impl FnMut(usize),
+    ) {
+        let transitions = &self.transitions[state];
+        let targets = if find_range(&self.alphabet_ranges, alphabet).is_some() {
+            transitions.get(alphabet)
+        } else {
+            transitions.get_default()
+        };
+        for &target in targets.into_iter().flatten() {
+            if marks.contains(target) {
+                continue;
+            }
+            for &to in &self.epsilon_closures[target] {
+                if marks.insert(to) {
+                    add(to);
+                }
+            }
+        }
+    }
+}
+
+// A thread holds the NFA states of the search started at its position that no earlier thread
+// holds, so a step visits every state and closure entry at most once
+impl Search for &PikeVM {
+    type State = Vec<usize>;
+
+    fn get_start(&mut self) -> Option<Vec<usize>> {
+        Some(self.start_closure.clone())
+    }
+
+    fn is_final(&self, states: &Vec<usize>) -> bool {
+        states.iter().any(|&state| self.final_states[state])
+    }
+
+    fn step(&mut self, threads: &mut Vec<(Vec<usize>, usize)>, alphabet: char, marks: &mut Marks) {
+        threads.retain_mut(|(states, _)| {
+            let mut next_states = Vec::new();
+            for &state in states.iter() {
+                self.add_targets(state, alphabet, marks, |to| next_states.push(to));
+            }
+            *states = next_states;
+            !states.is_empty()
+        });
+    }
+}
+
+impl From<&EpsilonNFA> for PikeVM {
+    fn from(value: &EpsilonNFA) -> Self {
+        Self::new(value)
+    }
+}
@@ -1 +1,916 @@
-use std::ops::Add;
+use super::*;
+
+const META_CHARACTERS: [char; 9] = ['(', ')', '[', '.', '|', '*', '+', '?', '\\'];
+
+// Meta characters only once `RegexBuilder::set_operators` turns them on, literals otherwise
+const SET_OPERATORS: [char; 3] = ['&', '-', '~'];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Ast {
+    Empty,
+    Literal(char),
+    /// Any character of the sorted, disjoint and non adjacent ranges.
+    Class(Vec<CharRange>),
+    Concat(Vec<Ast>),
+    Alternation(Vec<Ast>),
+    Star(Box<Ast>),
+    Plus(Box<Ast>),
+    Optional(Box<Ast>),
+    /// A capture group and its index, groups being numbered from 1 by their opening parenthesis.
+    Group(usize, Box<Ast>),
+    Intersection(Box<Ast>, Box<Ast>),
+    Difference(Box<Ast>, Box<Ast>),
+    Complement(Box<Ast>),
+}
+
+impl Ast {
+    // The atoms the character ranges of the pattern split into
+    pub(crate) fn get_alphabets(&self) -> Vec<CharRange> {
+        let mut alphabets = Vec::new();
+        let mut stack = vec![self];
+        while let Some(ast) = stack.pop() {
+            match ast {
+                Self::Empty => {}
+                Self::Literal(ch) => alphabets.push(CharRange::from(*ch)),
+                Self::Class(ranges) => alphabets.extend(ranges),
+                Self::Concat(items) | Self::Alternation(items) => stack.extend(items),
+                Self::Star(inner)
+                | Self::Plus(inner)
+                | Self::Optional(inner)
+                | Self::Group(_, inner)
+                | Self::Complement(inner) => stack.push(inner),
+                Self::Intersection(left, right) | Self::Difference(left, right) => {
+                    stack.extend([left.as_ref(), right.as_ref()]);
+                }
+            }
+        }
+        split_into_atoms(alphabets)
+    }
+}
+
+// Recursive descent parser for the grammar
+//     alternation  := intersection ('|' intersection)*
+//     intersection := concat (('&' | '-') concat)*
+//     concat       := complement*
+//     complement   := '~' complement | repeat
+//     repeat       := atom ('*' | '+' | '?')*
+//     atom         := '(' group alternation ')' | '[' class ']' | '.' | '\' char | char
+//     group        := '' | '?:' | '?<' name '>' | '?P<' name '>'
+//     class        := '^'? item+
+//     item         := char ('-' char)? | '\' char ('-' char)?
+// where '&', '-' and '~' are only operators with `set_operators`, and plain characters otherwise.
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    set_operators: bool,
+    // The names of the capture groups, the whole match being the unnamed group 0
+    group_names: Vec<Option<String>>,
+}
+
+impl Parser {
+    fn new(pattern: &str, set_operators: bool) -> Self {
+        Self {
+            chars: pattern.chars().collect(),
+            position: 0,
+            set_operators,
+            group_names: vec![None],
+        }
+    }
+
+    fn is_meta(&self, ch: char) -> bool {
+        META_CHARACTERS.contains(&ch) || self.set_operators && SET_OPERATORS.contains(&ch)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek();
+        if ch.is_some() {
+            self.position += 1;
+        }
+        ch
+    }
+
+    fn parse(mut self) -> Result<(Ast, Vec<Option<String>>), CustomError> {
+        let ast = self.parse_alternation()?;
+        if self.peek().is_some() {
+            // Only an unmatched ')' can stop the top level alternation early
+            return Err(CustomError::InvalidRegex);
+        }
+        Ok((ast, self.group_names))
+    }
+
+    fn parse_alternation(&mut self) -> Result<Ast, CustomError> {
+        let mut branches = vec![self.parse_intersection()?];
+        while self.peek() == Some('|') {
+            self.next();
+            branches.push(self.parse_intersection()?);
+        }
+        if branches.len() == 1 {
+            return Ok(branches.pop().unwrap());
+        }
+        Ok(Ast::Alternation(branches))
+    }
+
+    fn parse_intersection(&mut self) -> Result<Ast, CustomError> {
+        let mut ast = self.parse_concat()?;
+        while let Some(ch) = self.peek().filter(|_| self.set_operators) {
+            ast = match ch {
+                '&' => {
+                    self.next();
+                    Ast::Intersection(Box::new(ast), Box::new(self.parse_concat()?))
+                }
+                '-' => {
+                    self.next();
+                    Ast::Difference(Box::new(ast), Box::new(self.parse_concat()?))
+                }
+                _ => break,
+            };
+        }
+        Ok(ast)
+    }
+
+    fn parse_concat(&mut self) -> Result<Ast, CustomError> {
+        let mut items = Vec::new();
+        while let Some(ch) = self.peek() {
+            if matches!(ch, '|' | ')') || self.set_operators && matches!(ch, '&' | '-') {
+                break;
+            }
+            items.push(self.parse_complement()?);
+        }
+        Ok(match items.len() {
+            0 => Ast::Empty,
+            1 => items.pop().unwrap(),
+            _ => Ast::Concat(items),
+        })
+    }
+
+    fn parse_complement(&mut self) -> Result<Ast, CustomError> {
+        if self.set_operators && self.peek() == Some('~') {
+            self.next();
+            return Ok(Ast::Complement(Box::new(self.parse_complement()?)));
+        }
+        self.parse_repeat()
+    }
+
+    fn parse_repeat(&mut self) -> Result<Ast, CustomError> {
+        let mut ast = self.parse_atom()?;
+        while let Some(ch) = self.peek() {
+            ast = match ch {
+                '*' => Ast::Star(Box::new(ast)),
+                '+' => Ast::Plus(Box::new(ast)),
+                '?' => Ast::Optional(Box::new(ast)),
+                _ => break,
+            };
+            self.next();
+        }
+        Ok(ast)
+    }
+
+    fn parse_atom(&mut self) -> Result<Ast, CustomError> {
+        match self.next() {
+            Some('(') => {
+                let group = self.parse_group()?;
+                let ast = self.parse_alternation()?;
+                if self.next() != Some(')') {
+                    return Err(CustomError::InvalidRegex);
+                }
+                Ok(match group {
+                    Some(index) => Ast::Group(index, Box::new(ast)),
+                    None => ast,
+                })
+            }
+            Some('[') => self.parse_class(),
+            Some('.') => Ok(Ast::Class(complement_ranges([CharRange::from('\n')]))),
+            Some('\\') => match self.next() {
+                Some(ch) => match get_escaped_class(ch) {
+                    Some(class) => Ok(Ast::Class(class)),
+                    None => Ok(Ast::Literal(get_escaped_char(ch)?)),
+                },
+                None => Err(CustomError::InvalidRegex),
+            },
+            Some(ch) if !self.is_meta(ch) => Ok(Ast::Literal(ch)),
+            // A quantifier with nothing to repeat, or a stray ')'
+            _ => Err(CustomError::InvalidRegex),
+        }
+    }
+
+    // Reads a character class after '['. A ']' right after the opening bracket and a '-' at
+    // either end are literal characters.
+    fn parse_class(&mut self) -> Result<Ast, CustomError> {
+        let is_negated = self.peek() == Some('^');
+        if is_negated {
+            self.next();
+        }
+        let mut ranges = Vec::new();
+        let mut is_first = true;
+        loop {
+            let start = match self.next() {
+                Some(']') if !is_first => break,
+                Some('\\') => {
+                    let ch = self.next().ok_or(CustomError::InvalidRegex)?;
+                    if let Some(class) = get_escaped_class(ch) {
+                        ranges.extend(class);
+                        is_first = false;
+                        continue;
+                    }
+                    get_escaped_char(ch)?
+                }
+                Some(ch) => ch,
+                None => return Err(CustomError::InvalidRegex),
+            };
+            is_first = false;
+            let is_range = self.peek() == Some('-')
+                && self
+                    .chars
+                    .get(self.position + 1)
+                    .is_some_and(|&ch| ch != ']');
+            if !is_range {
+                ranges.push(CharRange::from(start));
+                continue;
+            }
+            self.next();
+            let end = match self.next() {
+                Some('\\') => match self.next() {
+                    Some(ch) if get_escaped_class(ch).is_none() => get_escaped_char(ch)?,
+                    _ => return Err(CustomError::InvalidRegex),
+                },
+                Some(ch) => ch,
+                None => return Err(CustomError::InvalidRegex),
+            };
+            if end < start {
+                return Err(CustomError::InvalidRegex);
+            }
+            ranges.push(CharRange::new(start, end));
+        }
+        Ok(Ast::Class(if is_negated {
+            complement_ranges(ranges)
+        } else {
+            normalize_ranges(ranges)
+        }))
+    }
+
+    // Reads the group prefix after '(' and returns the index of the new capture group, or
+    // `None` for a non-capturing group
+    fn parse_group(&mut self) -> Result<Option<usize>, CustomError> {
+        if self.peek() != Some('?') {
+            self.group_names.push(None);
+            return Ok(Some(self.group_names.len() - 1));
+        }
+        self.next();
+        match self.next() {
+            Some(':') => return Ok(None),
+            Some('<') => {}
+            Some('P') if self.next() == Some('<') => {}
+            _ => return Err(CustomError::InvalidRegex),
+        }
+        let mut name = String::new();
+        loop {
+            match self.next() {
+                Some('>') if !name.is_empty() => break,
+                Some(ch) if ch.is_alphanumeric() || ch == '_' => name.push(ch),
+                _ => return Err(CustomError::InvalidRegex),
+            }
+        }
+        if name.starts_with(|ch: char| ch.is_ascii_digit())
+            || self.group_names.contains(&Some(name.clone()))
+        {
+            return Err(CustomError::InvalidRegex);
+        }
+        self.group_names.push(Some(name));
+        Ok(Some(self.group_names.len() - 1))
+    }
+}
+
+// The ranges of the class escapes `\d`, `\w` and `\s`, ASCII only, and of their negations over
+// the whole Unicode range
+fn get_escaped_class(ch: char) -> Option<Vec<CharRange>> {
+    let ranges = match ch.to_ascii_lowercase() {
+        'd' => vec![CharRange::from('0'..='9')],
+        'w' => vec![
+            CharRange::from('0'..='9'),
+            CharRange::from('A'..='Z'),
+            CharRange::from('_'),
+            CharRange::from('a'..='z'),
+        ],
+        's' => vec![CharRange::from('\t'..='\r'), CharRange::from(' ')],
+        _ => return None,
+    };
+    Some(if ch.is_ascii_uppercase() {
+        complement_ranges(ranges)
+    } else {
+        ranges
+    })
+}
+
+// The character an escape other than a class stands for: the control characters `\n`, `\t` and
+// `\r`, or the character itself. The other ASCII letters and digits are reserved.
+fn get_escaped_char(ch: char) -> Result<char, CustomError> {
+    match ch {
+        'n' => Ok('\n'),
+        't' => Ok('\t'),
+        'r' => Ok('\r'),
+        _ if ch.is_ascii_alphanumeric() => Err(CustomError::InvalidRegex),
+        _ => Ok(ch),
+    }
+}
+
+// Thompson construction: every fragment has exactly one start and one end state
+// and the pieces are glued together with epsilon (None) transitions. The operators with no
+// Thompson construction go through the product constructions on DFAs, and the resulting DFA
+// is copied in as a fragment.
+#[derive(Default)]
+struct Compiler {
+    states: HashSet<State>,
+    alphabets: HashSet<Option<CharRange>>,
+    transition_table: HashMap<(State, Option<CharRange>), HashSet<State>>,
+    // The atoms of every character range of the whole pattern
+    pattern_alphabets: Vec<CharRange>,
+}
+
+impl Compiler {
+    fn new(pattern_alphabets: Vec<CharRange>) -> Self {
+        Self {
+            pattern_alphabets,
+            ..Self::default()
+        }
+    }
+
+    fn into_epsilon_nfa(mut self, ast: &Ast) -> Result<EpsilonNFA, CustomError> {
+        let (start, end) = self.compile(ast);
+        EpsilonNFA::new_unoptimized(
+            self.states,
+            self.alphabets,
+            self.transition_table,
+            HashSet::from([start]),
+            HashSet::from([end]),
+        )
+    }
+
+    fn new_state(&mut self) -> State {
+        let state = State::new(self.states.len());
+        self.states.insert(state);
+        state
+    }
+
+    fn add_transition(&mut self, from: State, to: State, alphabet: Option<CharRange>) {
+        self.alphabets.insert(alphabet);
+        self.transition_table
+            .entry((from, alphabet))
+            .or_default()
+            .insert(to);
+    }
+
+    fn compile(&mut self, ast: &Ast) -> (State, State) {
+        match ast {
+            Ast::Empty => {
+                let start = self.new_state();
+                let end = self.new_state();
+                self.add_transition(start, end, None);
+                (start, end)
+            }
+            Ast::Literal(ch) => {
+                let start = self.new_state();
+                let end = self.new_state();
+                self.add_transition(start, end, Some(CharRange::from(*ch)));
+                (start, end)
+            }
+            Ast::Class(ranges) => {
+                let start = self.new_state();
+                let end = self.new_state();
+                for &range in ranges {
+                    self.add_transition(start, end, Some(range));
+                }
+                (start, end)
+            }
+            Ast::Concat(items) => {
+                let (start, mut end) = self.compile(&items[0]);
+                for item in &items[1..] {
+                    let (item_start, item_end) = self.compile(item);
+                    self.add_transition(end, item_start, None);
+                    end = item_end;
+                }
+                (start, end)
+            }
+            Ast::Alternation(branches) => {
+                let start = self.new_state();
+                let end = self.new_state();
+                for branch in branches {
+                    let (branch_start, branch_end) = self.compile(branch);
+                    self.add_transition(start, branch_start, None);
+                    self.add_transition(branch_end, end, None);
+                }
+                (start, end)
+            }
+            Ast::Star(inner) => {
+                let start = self.new_state();
+                let (inner_start, inner_end) = self.compile(inner);
+                let end = self.new_state();
+                self.add_transition(start, inner_start, None);
+                self.add_transition(start, end, None);
+                self.add_transition(inner_end, inner_start, None);
+                self.add_transition(inner_end, end, None);
+                (start, end)
+            }
+            Ast::Plus(inner) => {
+                let start = self.new_state();
+                let (inner_start, inner_end) = self.compile(inner);
+                let end = self.new_state();
+                self.add_transition(start, inner_start, None);
+                self.add_transition(inner_end, inner_start, None);
+                self.add_transition(inner_end, end, None);
+                (start, end)
+            }
+            Ast::Optional(inner) => {
+                let start = self.new_state();
+                let (inner_start, inner_end) = self.compile(inner);
+                let end = self.new_state();
+                self.add_transition(start, inner_start, None);
+                self.add_transition(start, end, None);
+                self.add_transition(inner_end, end, None);
+                (start, end)
+            }
+            Ast::Group(_, inner) => self.compile(inner),
+            Ast::Intersection(..) | Ast::Difference(..) | Ast::Complement(..) => {
+                let dfa = compile_operator(ast, &self.pattern_alphabets);
+                self.compile_dfa(&dfa)
+            }
+        }
+    }
+
+    // Copies the states of `dfa` that can still reach a final state into a fragment
+    fn compile_dfa(&mut self, dfa: &DFA) -> (State, State) {
+        let mapping: HashMap<State, State> = dfa
+            .get_states()
+            .iter()
+            .sorted_unstable()
+            .map(|&state| (state, self.new_state()))
+            .collect();
+        let start = self.new_state();
+        let end = self.new_state();
+        self.add_transition(start, mapping[dfa.get_start_state()], None);
+        // The NFA of the pattern has no default edges, so they become the ranges they read
+        for &from in dfa.get_states() {
+            for (alphabet, to) in dfa.get_explicit_transitions(from) {
+                if !dfa.get_trapped_states().contains(&to) {
+                    self.add_transition(mapping[&from], mapping[&to], Some(alphabet));
+                }
+            }
+        }
+        for final_state in dfa.get_final_states() {
+            self.add_transition(mapping[final_state], end, None);
+        }
+        (start, end)
+    }
+}
+
+// Builds the minimized DFA of an intersection, difference or complement, where the complement
+// is taken over all the strings of any characters
+pub(crate) fn compile_operator(ast: &Ast, pattern_alphabets: &[CharRange]) -> DFA {
+    let compile = |inner: &Ast| {
+        Compiler::new(pattern_alphabets.to_vec())
+            .into_epsilon_nfa(inner)
+            .unwrap()
+            .to_dfa()
+    };
+    let dfa = match ast {
+        Ast::Intersection(left, right) => &compile(left) & &compile(right),
+        Ast::Difference(left, right) => &compile(left) - &compile(right),
+        Ast::Complement(inner) => {
+            // Every string, from a single final state whose default edge reads any character
+            let state = State::new(0);
+            let everything = DFA::from_range_maps_unoptimized(
+                HashSet::from([state]),
+                HashSet::new(),
+                HashMap::from([(state, RangeMap::from_sorted(Vec::new(), Some(state)))]),
+                state,
+                HashSet::from([state]),
+            )
+            .unwrap();
+            &everything - &compile(inner)
+        }
+        _ => compile(ast),
+    };
+    dfa.get_minimized()
+}
+
+/// Parses `pattern` into a Thompson style epsilon NFA.
+///
+/// Supported syntax is concatenation, alternation (`|`), the quantifiers `*`, `+` and `?`,
+/// grouping with parentheses and `\` to escape any of the meta characters `()[.|*+?\`.
+/// A group is a capture group unless it starts with `?:`, and `?<name>` or `?P<name>` name it;
+/// the groups only matter to [`Regex::captures`]. `\n`, `\t` and `\r` are the control
+/// characters, and escaping any other ASCII letter or digit is an error.
+///
+/// Character classes such as `[a-z0-9_]` and their negations `[^...]` are taken over all of
+/// Unicode, and `.` matches any character but `\n`. The escapes `\d`, `\w` and `\s` are the
+/// ASCII digits, word characters and whitespace, `\D`, `\W` and `\S` their negations, and they
+/// also work inside a class. A class label is a [`CharRange`], so a negated class takes a few
+/// transitions rather than one per character.
+///
+/// `&`, `-` and `~` are plain characters, unless [`RegexBuilder::set_operators`] turns them
+/// into the set operators.
+///
+/// ```
+/// use re_rs::{compile_regex, CustomError};
+///
+/// let hex = compile_regex("0x[0-9a-fA-F]+").unwrap();
+/// assert!(hex.accepts("0x1F") && !hex.accepts("0xg"));
+/// let not_quote = compile_regex("\"[^\"]*\"").unwrap();
+/// assert!(not_quote.accepts("\"héllo wörld\"") && !not_quote.accepts("\"a\"b\""));
+/// let pair = compile_regex("\\w+-\\d+").unwrap();
+/// assert!(pair.accepts("a-1") && !pair.accepts("a1"));
+/// assert!(compile_regex(".\\S").unwrap().accepts("€x"));
+/// assert!(!compile_regex(".").unwrap().accepts("\n"));
+///
+/// let lines = compile_regex("a\\r?\\n[\\t ]*b").unwrap();
+/// assert!(lines.accepts("a\r\n\tb") && !lines.accepts("anb"));
+/// assert_eq!(compile_regex("\\q").err(), Some(CustomError::InvalidRegex));
+/// assert_eq!(compile_regex("[a-\\k]").err(), Some(CustomError::InvalidRegex));
+/// ```
+pub fn parse_regex(pattern: &str) -> Result<EpsilonNFA, CustomError> {
+    RegexBuilder::new(pattern).build_epsilon_nfa()
+}
+
+fn compile_ast(ast: &Ast) -> Result<EpsilonNFA, CustomError> {
+    Compiler::new(ast.get_alphabets()).into_epsilon_nfa(ast)
+}
+
+/// Parses `pattern` and runs it through the subset construction and minimization.
+///
+/// ```
+/// use re_rs::{compile_regex, parse_regex, CustomError};
+///
+/// let dfa = compile_regex("ab*a").unwrap();
+/// assert!(dfa.is_equivalent(&parse_regex("ab*a").unwrap().to_dfa()));
+/// let found: Vec<(_, &str)> = dfa.find_all("bbabbabbabbbaabaaaaba").unwrap().collect();
+/// assert_eq!(
+///     found,
+///     [(2..6, "abba"), (8..13, "abbba"), (13..16, "aba"), (16..18, "aa"), (18..21, "aba")]
+/// );
+/// assert_eq!(compile_regex("a(b|").err(), Some(CustomError::InvalidRegex));
+/// ```
+pub fn compile_regex(pattern: &str) -> Result<DFA, CustomError> {
+    RegexBuilder::new(pattern).build_dfa()
+}
+
+/// Builds a pattern with the syntax options that [`parse_regex`], [`compile_regex`] and
+/// [`Regex::new`] leave off.
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    pattern: String,
+    set_operators: bool,
+}
+
+impl RegexBuilder {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            set_operators: false,
+        }
+    }
+
+    /// Reads `&`, `-` and `~` as operators rather than plain characters, `\` escaping them.
+    ///
+    /// As the pattern ends up as a DFA, it then supports the intersection `A&B`, the difference
+    /// `A-B` and the complement `~A`, which matches every string that `A` does not match,
+    /// whatever its characters. `&` and `-` bind tighter than `|` and looser than
+    /// concatenation, and `~` applies to the repetition that follows it. The capture
+    /// groups inside these operators never take part in a match. A `-` inside a class is still
+    /// a range.
+    ///
+    /// ```
+    /// use re_rs::{compile_regex, RegexBuilder};
+    ///
+    /// let compile = |pattern| RegexBuilder::new(pattern).set_operators(true).build_dfa().unwrap();
+    ///
+    /// // Identifiers that are not keywords
+    /// let identifiers = compile("(f|i|n|o|r)+-(if|for)");
+    /// assert!(identifiers.accepts("fir") && identifiers.accepts("iff"));
+    /// assert!(!identifiers.accepts("for") && !identifiers.accepts("if"));
+    ///
+    /// // Words with both an `a` and a `b`, and words without `ab`
+    /// let both = compile("(a|b|c)*a(a|b|c)*&(a|b|c)*b(a|b|c)*");
+    /// assert!(both.accepts("cbca") && !both.accepts("aca"));
+    /// let no_ab = compile("~((a|b)*ab(a|b)*)&(a|b)*");
+    /// assert!(no_ab.is_equivalent(&compile_regex("b*a*").unwrap()));
+    ///
+    /// // Anything but `a`, over every character
+    /// let not_a = compile("~(a)");
+    /// assert!(not_a.accepts("b") && not_a.accepts("é") && not_a.accepts(""));
+    /// assert!(!not_a.accepts("a"));
+    ///
+    /// // Words that are not all digits, and an escaped operator
+    /// let words = compile("\\w+-\\d+");
+    /// assert!(words.accepts("a1") && !words.accepts("42"));
+    /// assert!(compile("a\\-b").accepts("a-b"));
+    ///
+    /// // Off by default
+    /// assert!(compile_regex("a-b").unwrap().accepts("a-b"));
+    /// ```
+    pub fn set_operators(&mut self, set_operators: bool) -> &mut Self {
+        self.set_operators = set_operators;
+        self
+    }
+
+    /// Builds the Thompson style epsilon NFA of the pattern, as [`parse_regex`] does.
+    pub fn build_epsilon_nfa(&self) -> Result<EpsilonNFA, CustomError> {
+        let (ast, _) = self.parse()?;
+        compile_ast(&ast)
+    }
+
+    /// Builds the minimized DFA of the pattern, as [`compile_regex`] does.
+    pub fn build_dfa(&self) -> Result<DFA, CustomError> {
+        let mut dfa = self.build_epsilon_nfa()?.to_dfa();
+        dfa.minimize();
+        Ok(dfa)
+    }
+
+    /// Builds a [`Regex`], as [`Regex::new`] does.
+    pub fn build(&self) -> Result<Regex, CustomError> {
+        let (ast, group_names) = self.parse()?;
+        let mut dfa = compile_ast(&ast)?.to_dfa();
+        dfa.minimize();
+        Ok(Regex {
+            pattern: self.pattern.clone(),
+            matcher: OptimizedDFA::from(&dfa),
+            dfa,
+            program: CaptureProgram::new(&ast, group_names),
+            match_kind: MatchKind::default(),
+        })
+    }
+
+    fn parse(&self) -> Result<(Ast, Vec<Option<String>>), CustomError> {
+        Parser::new(&self.pattern, self.set_operators).parse()
+    }
+}
+
+/// A single match of a [`Regex`] inside a haystack.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Match<'t> {
+    text: &'t str,
+    start: usize,
+    end: usize,
+}
+
+impl<'t> Match<'t> {
+    pub(crate) fn new(text: &'t str, range: Range<usize>) -> Self {
+        Self {
+            text,
+            start: range.start,
+            end: range.end,
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn as_str(&self) -> &'t str {
+        &self.text[self.range()]
+    }
+}
+
+/// A compiled regular expression.
+///
+/// The pattern is parsed, determinized and minimized once in [`Regex::new`], so a `Regex` is
+/// meant to be built once and reused, e.g. from a `static` [`std::sync::LazyLock`].
+/// Characters that never appear in the pattern simply never take part in a match.
+///
+/// ```
+/// use re_rs::Regex;
+/// use std::sync::LazyLock;
+///
+/// static AB_STAR_A: LazyLock<Regex> = LazyLock::new(|| Regex::new("ab*a").unwrap());
+///
+/// let text = "bbabbabbabbbaabaaaaba";
+/// assert!(AB_STAR_A.is_match(text));
+/// assert_eq!(AB_STAR_A.find(text).map(|found| found.as_str()), Some("abba"));
+/// let found: Vec<&str> = AB_STAR_A.find_iter(text).map(|found| found.as_str()).collect();
+/// assert_eq!(found, ["abba", "abbba", "aba", "aa", "aba"]);
+/// let parts: Vec<&str> = AB_STAR_A.split(text).collect();
+/// assert_eq!(parts, ["bb", "bb", "", "", "", ""]);
+/// assert_eq!(AB_STAR_A.replace(text, "_"), "bb_bbabbbaabaaaaba");
+/// assert_eq!(AB_STAR_A.replace_all(text, "_"), "bb_bb____");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Regex {
+    pattern: String,
+    dfa: DFA,
+    matcher: OptimizedDFA<'static>,
+    program: CaptureProgram,
+    match_kind: MatchKind,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, CustomError> {
+        RegexBuilder::new(pattern).build()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn get_dfa(&self) -> &DFA {
+        &self.dfa
+    }
+
+    pub fn get_match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Selects the matches of [`Regex::find`] and the other searches. The DFA finds the
+    /// leftmost-longest and shortest matches, and the capture program, which keeps the order of
+    /// the alternatives, the leftmost-first ones.
+    pub fn set_match_kind(&mut self, match_kind: MatchKind) {
+        self.match_kind = match_kind;
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    pub fn find<'t>(&self, text: &'t str) -> Option<Match<'t>> {
+        self.find_iter(text).next()
+    }
+
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> Matches<'r, 't> {
+        Matches {
+            iterator: MatchIterator::new(
+                match self.match_kind {
+                    MatchKind::LeftmostFirst => Searcher::Program(&self.program),
+                    _ => Searcher::Compiled(Cow::Borrowed(&self.matcher)),
+                },
+                text,
+                self.match_kind,
+            ),
+            text,
+        }
+    }
+
+    /// The number of capture groups, counting the whole match as group 0.
+    pub fn captures_len(&self) -> usize {
+        self.program.get_group_names().len()
+    }
+
+    /// The names of the capture groups in order, `None` for the whole match and for the
+    /// unnamed groups.
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> {
+        self.program.get_group_names().iter().map(Option::as_deref)
+    }
+
+    /// Finds the leftmost match as [`Regex::find`] does, along with the spans of its capture
+    /// groups.
+    ///
+    /// The match itself follows the [`MatchKind`]; inside it the groups are resolved by
+    /// priority, as a backtracking engine would: the earlier alternative first and the
+    /// quantifiers greedy. A group inside a repetition reports its last iteration and a
+    /// group that took no part in the match is `None`.
+    ///
+    /// ```
+    /// use re_rs::Regex;
+    ///
+    /// let regex = Regex::new("(?<key>(a|b)+)=(a*)(b)?").unwrap();
+    /// let captures = regex.captures("xx abba=aa").unwrap();
+    /// assert_eq!(captures.get(0).unwrap().as_str(), "abba=aa");
+    /// assert_eq!(captures.name("key").unwrap().range(), 3..7);
+    /// assert_eq!(captures.get(2).unwrap().as_str(), "a");
+    /// assert_eq!(captures.get(3).unwrap().as_str(), "aa");
+    /// assert_eq!(captures.get(4), None);
+    /// ```
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        self.captures_iter(text).next()
+    }
+
+    /// Iterates over the same non-overlapping matches as [`Regex::find_iter`], with their
+    /// capture groups.
+    ///
+    /// ```
+    /// use re_rs::Regex;
+    ///
+    /// let regex = Regex::new("(1|2|3)+-(?<to>(1|2|3)+)").unwrap();
+    /// let ranges: Vec<(String, String)> = regex
+    ///     .captures_iter("12-3, 31-122")
+    ///     .map(|captures| (captures[1].to_string(), captures["to"].to_string()))
+    ///     .collect();
+    /// assert_eq!(ranges, [("2".into(), "3".into()), ("1".into(), "122".into())]);
+    /// ```
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t str) -> CaptureMatches<'r, 't> {
+        CaptureMatches {
+            matches: self.find_iter(text),
+            program: &self.program,
+        }
+    }
+
+    pub fn split<'r, 't>(&'r self, text: &'t str) -> Split<'r, 't> {
+        Split {
+            matches: self.find_iter(text),
+            text,
+            last: Some(0),
+        }
+    }
+
+    /// Replaces the leftmost match with `replacement`.
+    pub fn replace(&self, text: &str, replacement: &str) -> String {
+        self.replacen(text, 1, replacement)
+    }
+
+    /// Replaces every non-overlapping match with `replacement`, the empty ones included.
+    ///
+    /// ```
+    /// use re_rs::Regex;
+    ///
+    /// let regex = Regex::new("x*").unwrap();
+    /// assert_eq!(regex.replace_all("ab", "-"), "-a-b-");
+    /// assert_eq!(regex.replace_all("", "-"), "-");
+    /// assert!(Regex::new("a*").unwrap().is_match(""));
+    /// ```
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        self.replacen(text, usize::MAX, replacement)
+    }
+
+    fn replacen(&self, text: &str, limit: usize, replacement: &str) -> String {
+        let mut replaced = String::with_capacity(text.len());
+        let mut last = 0;
+        for found in self.find_iter(text).take(limit) {
+            replaced.push_str(&text[last..found.start()]);
+            replaced.push_str(replacement);
+            last = found.end();
+        }
+        replaced.push_str(&text[last..]);
+        replaced
+    }
+}
+
+impl FromStr for Regex {
+    type Err = CustomError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        Self::new(pattern)
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+/// Iterator over the non-overlapping matches of a [`Regex`], created by [`Regex::find_iter`].
+pub struct Matches<'r, 't> {
+    iterator: MatchIterator<'r, 't>,
+    pub(crate) text: &'t str,
+}
+
+impl<'t> Iterator for Matches<'_, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iterator
+            .next()
+            .map(|range| Match::new(self.text, range))
+    }
+}
+
+/// Iterator over the substrings between the matches of a [`Regex`], created by [`Regex::split`].
+pub struct Split<'r, 't> {
+    matches: Matches<'r, 't>,
+    text: &'t str,
+    last: Option<usize>,
+}
+
+impl<'t> Iterator for Split<'_, 't> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last = self.last?;
+        match self.matches.next() {
+            Some(found) => {
+                self.last = Some(found.end());
+                Some(&self.text[last..found.start()])
+            }
+            None => {
+                self.last = None;
+                Some(&self.text[last..])
+            }
+        }
+    }
+}
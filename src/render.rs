@@ -0,0 +1,455 @@
+use super::*;
+
+/// Output formats understood by [`Render::render`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DiagramFormat {
+    /// Graphviz DOT.
+    Dot,
+    /// Mermaid `stateDiagram-v2`.
+    Mermaid,
+    /// TikZ picture for the `automata`, `positioning` and `arrows.meta` libraries.
+    Tikz,
+}
+
+/// Diagram export for automata.
+///
+/// States are laid out in the order of `get_topologically_sorted`, with the start states first
+/// and trapped states last, and edges follow the order of their states, so rendering the same
+/// automaton twice gives the same text. Final states are drawn as double circles (accepting),
+/// trapped states dashed and gray, and the alphabets of parallel edges are merged into a single
+/// label with ranges such as `a-z`.
+///
+/// ```
+/// use re_rs::{compile_regex, Render};
+///
+/// let dfa = compile_regex("a(b|c|d)*e").unwrap();
+///
+/// let dot = dfa.to_dot();
+/// assert!(dot.starts_with("digraph {"));
+/// assert!(dot.contains("[label=\"b-d\"]"));
+/// assert!(dot.contains("[shape=doublecircle]"));
+///
+/// let mermaid = dfa.to_mermaid();
+/// assert!(mermaid.starts_with("stateDiagram-v2"));
+/// assert!(mermaid.contains("[*] --> q0"));
+///
+/// let tikz = dfa.to_tikz();
+/// assert!(tikz.contains("\\node[state, initial] (q0) {$q_{0}$};"));
+/// assert_eq!(tikz, dfa.clone().to_tikz());
+/// ```
+pub trait Render {
+    fn render(&self, format: DiagramFormat) -> String;
+
+    fn to_dot(&self) -> String {
+        self.render(DiagramFormat::Dot)
+    }
+
+    fn to_mermaid(&self) -> String {
+        self.render(DiagramFormat::Mermaid)
+    }
+
+    fn to_tikz(&self) -> String {
+        self.render(DiagramFormat::Tikz)
+    }
+}
+
+impl Render for DFA {
+    fn render(&self, format: DiagramFormat) -> String {
+        Diagram::from(self).render(format)
+    }
+}
+
+impl Render for EpsilonNFA {
+    fn render(&self, format: DiagramFormat) -> String {
+        Diagram::from(self).render(format)
+    }
+}
+
+// Automaton independent view used by the exporters: one entry per state and one edge per pair
+// of states, labelled with every alphabet that moves between them
+pub(crate) struct Diagram {
+    states: Vec<DiagramState>,
+    edges: Vec<(State, State, EdgeLabel)>,
+}
+
+struct DiagramState {
+    state: State,
+    is_start: bool,
+    is_final: bool,
+    is_trapped: bool,
+    provenance: Option<String>,
+}
+
+// What a transition reads: a range of characters, the empty string, or every character outside
+// the alphabet through a default edge
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Label {
+    Range(CharRange),
+    Epsilon,
+    Default,
+}
+
+// The alphabets of an edge, with runs of consecutive characters kept as inclusive ranges so that
+// every backend can escape the characters in its own way
+struct EdgeLabel {
+    has_epsilon: bool,
+    has_default: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl Diagram {
+    fn new(
+        states: Vec<State>,
+        start_states: &HashSet<State>,
+        final_states: &HashSet<State>,
+        trapped_states: &HashSet<State>,
+        provenance: Option<&HashMap<State, Provenance>>,
+        transitions: impl Iterator<Item = (State, Label, State)>,
+    ) -> Self {
+        let states_index: HashMap<State, usize> = states
+            .iter()
+            .enumerate()
+            .map(|(index, &state)| (state, index))
+            .collect();
+        let mut edge_alphabets: HashMap<(State, State), Vec<Label>> = HashMap::new();
+        for (from, alphabet, to) in transitions {
+            edge_alphabets.entry((from, to)).or_default().push(alphabet);
+        }
+        let edges = edge_alphabets
+            .into_iter()
+            .sorted_unstable_by_key(|&((from, to), _)| (states_index[&from], states_index[&to]))
+            .map(|((from, to), alphabets)| (from, to, EdgeLabel::new(alphabets)))
+            .collect();
+        Self {
+            states: states
+                .into_iter()
+                .map(|state| DiagramState {
+                    state,
+                    is_start: start_states.contains(&state),
+                    is_final: final_states.contains(&state),
+                    is_trapped: trapped_states.contains(&state),
+                    provenance: provenance
+                        .and_then(|provenance| provenance.get(&state))
+                        .map(|origin| origin.to_string()),
+                })
+                .collect(),
+            edges,
+        }
+    }
+
+    // Orders the states the same way as the transition table printed by `Display`
+    fn sort_states(
+        topologically_sorted: Vec<State>,
+        start_states: &HashSet<State>,
+        trapped_states: &HashSet<State>,
+    ) -> Vec<State> {
+        topologically_sorted
+            .into_iter()
+            .enumerate()
+            .sorted_unstable_by_key(|&(index, state)| {
+                (
+                    !start_states.contains(&state),
+                    trapped_states.contains(&state),
+                    index,
+                )
+            })
+            .map(|(_, state)| state)
+            .collect()
+    }
+
+    pub fn render(&self, format: DiagramFormat) -> String {
+        match format {
+            DiagramFormat::Dot => self.to_dot(),
+            DiagramFormat::Mermaid => self.to_mermaid(),
+            DiagramFormat::Tikz => self.to_tikz(),
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut lines = vec![
+            "digraph {".to_string(),
+            "    rankdir=LR;".to_string(),
+            "    node [shape=circle];".to_string(),
+        ];
+        for (index, diagram_state) in self.states.iter().enumerate() {
+            let mut attributes = Vec::new();
+            if diagram_state.is_final {
+                attributes.push("shape=doublecircle".to_string());
+            }
+            if diagram_state.is_trapped {
+                attributes.push("style=dashed, color=gray, fontcolor=gray".to_string());
+            }
+            if let Some(provenance) = &diagram_state.provenance {
+                attributes.push(format!("xlabel=\"{}\"", escape_dot(provenance)));
+            }
+            if attributes.is_empty() {
+                lines.push(format!("    {};", diagram_state.state));
+            } else {
+                lines.push(format!(
+                    "    {} [{}];",
+                    diagram_state.state,
+                    attributes.join(", ")
+                ));
+            }
+            if diagram_state.is_start {
+                lines.push(format!("    start{index} [shape=point];"));
+                lines.push(format!("    start{index} -> {};", diagram_state.state));
+            }
+        }
+        for (from, to, label) in &self.edges {
+            lines.push(format!(
+                "    {from} -> {to} [label=\"{}\"];",
+                escape_dot(&label.format("ε", escape_control))
+            ));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    fn to_mermaid(&self) -> String {
+        let mut lines = vec![
+            "stateDiagram-v2".to_string(),
+            "    direction LR".to_string(),
+        ];
+        for diagram_state in &self.states {
+            match &diagram_state.provenance {
+                Some(provenance) => lines.push(format!(
+                    "    {0} : {0} {1}",
+                    diagram_state.state,
+                    escape_mermaid(provenance)
+                )),
+                None => lines.push(format!("    {}", diagram_state.state)),
+            }
+        }
+        for diagram_state in self.states.iter().filter(|state| state.is_start) {
+            lines.push(format!("    [*] --> {}", diagram_state.state));
+        }
+        for (from, to, label) in &self.edges {
+            lines.push(format!(
+                "    {from} --> {to} : {}",
+                escape_mermaid(&label.format("ε", escape_control))
+            ));
+        }
+        for diagram_state in self.states.iter().filter(|state| state.is_final) {
+            lines.push(format!("    {} --> [*]", diagram_state.state));
+        }
+        let trapped_states = self
+            .states
+            .iter()
+            .filter(|state| state.is_trapped)
+            .map(|state| state.state)
+            .join(",");
+        if !trapped_states.is_empty() {
+            lines.push("    classDef trapped stroke-dasharray: 5 5, color: gray".to_string());
+            lines.push(format!("    class {trapped_states} trapped"));
+        }
+        lines.join("\n")
+    }
+
+    // The states are placed on a single row in layout order, which keeps the picture stable and
+    // leaves the fine tuning of positions to the document
+    fn to_tikz(&self) -> String {
+        let mut lines = vec![
+            "\\begin{tikzpicture}[shorten >=1pt, node distance=2.5cm, on grid, auto, >={Stealth[round]}]"
+                .to_string(),
+        ];
+        let mut previous: Option<State> = None;
+        for diagram_state in &self.states {
+            let mut options = vec!["state".to_string()];
+            if diagram_state.is_start {
+                options.push("initial".to_string());
+            }
+            if diagram_state.is_final {
+                options.push("accepting".to_string());
+            }
+            if diagram_state.is_trapped {
+                options.push("dashed, draw=gray, text=gray".to_string());
+            }
+            if let Some(previous) = previous {
+                options.push(format!("right=of {previous}"));
+            }
+            if let Some(provenance) = &diagram_state.provenance {
+                options.push(format!("label={{below:{{{}}}}}", escape_latex(provenance)));
+            }
+            let state = diagram_state.state;
+            lines.push(format!(
+                "    \\node[{}] ({state}) {{$q_{{{}}}$}};",
+                options.join(", "),
+                state.get_index()
+            ));
+            previous = Some(state);
+        }
+        if !self.edges.is_empty() {
+            let edges: HashSet<(State, State)> =
+                self.edges.iter().map(|&(from, to, _)| (from, to)).collect();
+            lines.push("    \\path[->]".to_string());
+            for (from, to, label) in &self.edges {
+                let placement = if from == to {
+                    " [loop above]"
+                } else if edges.contains(&(*to, *from)) {
+                    " [bend left]"
+                } else {
+                    ""
+                };
+                lines.push(format!(
+                    "        ({from}) edge{placement} node {{{}}} ({})",
+                    label.format("$\\varepsilon$", escape_latex_char),
+                    if from == to {
+                        String::new()
+                    } else {
+                        to.to_string()
+                    }
+                ));
+            }
+            lines.push("    ;".to_string());
+        }
+        lines.push("\\end{tikzpicture}".to_string());
+        lines.join("\n")
+    }
+}
+
+impl From<&DFA> for Diagram {
+    fn from(value: &DFA) -> Self {
+        let states = Self::sort_states(
+            value.get_topologically_sorted(),
+            &HashSet::from([*value.get_start_state()]),
+            value.get_trapped_states(),
+        );
+        Self::new(
+            states,
+            &HashSet::from([*value.get_start_state()]),
+            value.get_final_states(),
+            value.get_trapped_states(),
+            value.get_provenance(),
+            value
+                .get_transition_table()
+                .iter()
+                .flat_map(|(&from, transitions)| {
+                    transitions
+                        .get_ranges()
+                        .iter()
+                        .map(move |&(alphabet, to)| (from, Label::Range(alphabet), to))
+                        .chain(
+                            transitions
+                                .get_default()
+                                .map(|&to| (from, Label::Default, to)),
+                        )
+                }),
+        )
+    }
+}
+
+impl From<&EpsilonNFA> for Diagram {
+    fn from(value: &EpsilonNFA) -> Self {
+        let states = Self::sort_states(
+            value.get_topologically_sorted(),
+            value.get_start_states(),
+            &HashSet::new(),
+        );
+        Self::new(
+            states,
+            value.get_start_states(),
+            value.get_final_states(),
+            &HashSet::new(),
+            None,
+            value
+                .get_transition_table()
+                .iter()
+                .flat_map(|(&from, transitions)| {
+                    transitions
+                        .get_ranges()
+                        .iter()
+                        .map(|&(alphabet, ref to)| (Label::Range(alphabet), to))
+                        .chain(transitions.get_default().map(|to| (Label::Default, to)))
+                        .flat_map(move |(label, to)| to.iter().map(move |&to| (from, label, to)))
+                })
+                .chain(
+                    value
+                        .get_epsilon_transitions()
+                        .iter()
+                        .flat_map(|(&from, to)| {
+                            to.iter().map(move |&to| (from, Label::Epsilon, to))
+                        }),
+                ),
+        )
+    }
+}
+
+impl EdgeLabel {
+    // Collapses runs of consecutive characters into ranges
+    fn new(labels: Vec<Label>) -> Self {
+        let ranges = normalize_ranges(labels.iter().filter_map(|label| match label {
+            Label::Range(range) => Some(*range),
+            _ => None,
+        }))
+        .into_iter()
+        .map(|range| (range.get_start(), range.get_end()))
+        .collect();
+        Self {
+            has_epsilon: labels.contains(&Label::Epsilon),
+            has_default: labels.contains(&Label::Default),
+            ranges,
+        }
+    }
+
+    // Joins the alphabets with epsilon first and the default edge last as `else`; only runs of
+    // at least three characters are shown as a range such as `a-z`
+    fn format(&self, epsilon: &str, escape: impl Fn(char) -> String) -> String {
+        let mut parts = Vec::new();
+        if self.has_epsilon {
+            parts.push(epsilon.to_string());
+        }
+        for &(start, end) in &self.ranges {
+            if start == end {
+                parts.push(escape(start));
+            } else if next_char(start) == Some(end) {
+                parts.extend([escape(start), escape(end)]);
+            } else {
+                parts.push(format!("{}-{}", escape(start), escape(end)));
+            }
+        }
+        if self.has_default {
+            parts.push("else".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+fn escape_control(alphabet: char) -> String {
+    if !is_printable(alphabet) {
+        alphabet.escape_debug().to_string()
+    } else {
+        alphabet.to_string()
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Mermaid reads `#...;` as an entity code, so `#`, `;` and quotes are written as entity codes
+fn escape_mermaid(label: &str) -> String {
+    label
+        .chars()
+        .map(|ch| match ch {
+            '#' => "#35;".to_string(),
+            ';' => "#59;".to_string(),
+            '"' => "#quot;".to_string(),
+            _ => ch.to_string(),
+        })
+        .collect()
+}
+
+fn escape_latex(label: &str) -> String {
+    label.chars().map(escape_latex_char).collect()
+}
+
+fn escape_latex_char(alphabet: char) -> String {
+    match alphabet {
+        '\\' => "\\textbackslash{}".to_string(),
+        '~' => "\\textasciitilde{}".to_string(),
+        '^' => "\\textasciicircum{}".to_string(),
+        '{' | '}' | '$' | '&' | '#' | '_' | '%' => format!("\\{alphabet}"),
+        _ => escape_control(alphabet).replace('\\', "\\textbackslash{}"),
+    }
+}
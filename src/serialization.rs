@@ -0,0 +1,264 @@
+use super::*;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Serde support for the automata. Maps keyed by tuples have no representation in formats such as
+// JSON, so the automata go through flat mirror structs with sorted lists, and deserialization
+// goes through the validating constructors.
+
+#[derive(Serialize, Deserialize)]
+struct DFAData {
+    states: Vec<State>,
+    alphabets: Vec<CharRange>,
+    transitions: Vec<(State, CharRange, State)>,
+    #[serde(default)]
+    default_transitions: Vec<(State, State)>,
+    start_state: State,
+    final_states: Vec<State>,
+    #[serde(default)]
+    match_kind: MatchKind,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EpsilonNFAData {
+    states: Vec<State>,
+    alphabets: Vec<Option<CharRange>>,
+    transitions: Vec<(State, Option<CharRange>, Vec<State>)>,
+    #[serde(default)]
+    default_transitions: Vec<(State, Vec<State>)>,
+    start_states: Vec<State>,
+    final_states: Vec<State>,
+}
+
+// A single character, or the ends of a range as a pair
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum CharRangeData {
+    Single(char),
+    Range(char, char),
+}
+
+/// A single character is a plain character, a range the pair of its ends:
+///
+/// ```
+/// use re_rs::CharRange;
+///
+/// assert_eq!(serde_json::to_string(&CharRange::from('a')).unwrap(), r#""a""#);
+/// let digits: CharRange = serde_json::from_str(r#"["0", "9"]"#).unwrap();
+/// assert_eq!(digits, CharRange::from('0'..='9'));
+/// assert!(serde_json::from_str::<CharRange>(r#"["9", "0"]"#).is_err());
+/// ```
+impl Serialize for CharRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_single() {
+            CharRangeData::Single(self.get_start())
+        } else {
+            CharRangeData::Range(self.get_start(), self.get_end())
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CharRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match CharRangeData::deserialize(deserializer)? {
+            CharRangeData::Single(alphabet) => Ok(CharRange::from(alphabet)),
+            CharRangeData::Range(start, end) if start <= end => Ok(CharRange::new(start, end)),
+            CharRangeData::Range(..) => Err(D::Error::custom("the range ends before it starts")),
+        }
+    }
+}
+
+impl Serialize for DFA {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DFAData {
+            states: self
+                .get_states()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            alphabets: self
+                .get_alphabets()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            transitions: self
+                .get_transition_table()
+                .iter()
+                .flat_map(|(&from, transitions)| {
+                    transitions
+                        .get_ranges()
+                        .iter()
+                        .map(move |&(alphabet, to)| (from, alphabet, to))
+                })
+                .sorted_unstable()
+                .collect(),
+            default_transitions: self
+                .get_transition_table()
+                .iter()
+                .filter_map(|(&from, transitions)| Some((from, *transitions.get_default()?)))
+                .sorted_unstable()
+                .collect(),
+            start_state: *self.get_start_state(),
+            final_states: self
+                .get_final_states()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            match_kind: self.get_match_kind(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializes through [`DFA::from_range_maps`], so a table that is not total or leads outside
+/// the states, or a start state outside the states, is rejected with the matching
+/// [`CustomError`] as the message. The default edges are listed apart, and may be left out:
+///
+/// ```
+/// use re_rs::{compile_regex, CustomError, DFA};
+///
+/// let dfa = compile_regex("ab*").unwrap();
+/// let json = serde_json::to_string(&dfa).unwrap();
+/// assert!(serde_json::from_str::<DFA>(&json).unwrap().is_equivalent(&dfa));
+///
+/// let json = r#"{
+///     "states": [0, 1],
+///     "alphabets": ["a"],
+///     "transitions": [[0, "a", 1]],
+///     "start_state": 0,
+///     "final_states": [1]
+/// }"#;
+/// let error = serde_json::from_str::<DFA>(json).unwrap_err();
+/// assert!(error.to_string().contains(&CustomError::InvalidDFAKeys.to_string()));
+///
+/// let json = r#"{
+///     "states": [0],
+///     "alphabets": ["a"],
+///     "transitions": [[0, "a", 99]],
+///     "start_state": 0,
+///     "final_states": [0]
+/// }"#;
+/// let error = serde_json::from_str::<DFA>(json).unwrap_err();
+/// assert!(error.to_string().contains(&CustomError::InvalidDFAKeys.to_string()));
+///
+/// let dot = compile_regex(".").unwrap().get_minimized();
+/// let json = serde_json::to_string(&dot).unwrap();
+/// assert!(json.contains(r#""default_transitions":[[0,1],"#));
+/// assert!(serde_json::from_str::<DFA>(&json).unwrap().is_isomorphic(&dot));
+/// ```
+impl<'de> Deserialize<'de> for DFA {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = DFAData::deserialize(deserializer)?;
+        let alphabets = data.alphabets.into_iter().collect();
+        let mut transition_table = group_transitions(
+            &alphabets,
+            data.transitions
+                .into_iter()
+                .map(|(from, alphabet, to)| ((from, alphabet), to)),
+        )
+        .map_err(D::Error::custom)?;
+        for (from, to) in data.default_transitions {
+            transition_table
+                .entry(from)
+                .or_default()
+                .set_default(Some(to));
+        }
+        let mut dfa = DFA::from_range_maps(
+            data.states.into_iter().collect(),
+            alphabets,
+            transition_table,
+            data.start_state,
+            data.final_states.into_iter().collect(),
+        )
+        .map_err(D::Error::custom)?;
+        dfa.set_match_kind(data.match_kind)
+            .map_err(D::Error::custom)?;
+        Ok(dfa)
+    }
+}
+
+impl Serialize for EpsilonNFA {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sorted = |states: &HashSet<State>| states.iter().copied().sorted_unstable().collect();
+        EpsilonNFAData {
+            states: sorted(self.get_states()),
+            alphabets: self
+                .get_alphabets()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            transitions: self
+                .get_transition_table()
+                .iter()
+                .flat_map(|(&from, transitions)| {
+                    transitions
+                        .get_ranges()
+                        .iter()
+                        .map(move |(alphabet, to)| (from, Some(*alphabet), sorted(to)))
+                })
+                .chain(
+                    self.get_epsilon_transitions()
+                        .iter()
+                        .map(|(&from, to)| (from, None, sorted(to))),
+                )
+                .sorted_unstable()
+                .collect(),
+            default_transitions: self
+                .get_transition_table()
+                .iter()
+                .filter_map(|(&from, transitions)| Some((from, sorted(transitions.get_default()?))))
+                .sorted_unstable()
+                .collect(),
+            start_states: sorted(self.get_start_states()),
+            final_states: sorted(self.get_final_states()),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializes through [`EpsilonNFA::new_unoptimized`], with `null` as the epsilon alphabet
+/// and the default edges added afterwards:
+///
+/// ```
+/// use re_rs::{parse_regex, EpsilonNFA};
+///
+/// let nfa = parse_regex("a|b*").unwrap();
+/// let json = serde_json::to_string(&nfa).unwrap();
+/// let parsed: EpsilonNFA = serde_json::from_str(&json).unwrap();
+/// assert!(parsed.to_dfa().is_equivalent(&nfa.to_dfa()));
+/// ```
+impl<'de> Deserialize<'de> for EpsilonNFA {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = EpsilonNFAData::deserialize(deserializer)?;
+        let mut transition_table: HashMap<(State, Option<CharRange>), HashSet<State>> =
+            HashMap::new();
+        for (from, alphabet, to) in data.transitions {
+            transition_table
+                .entry((from, alphabet))
+                .or_default()
+                .extend(to);
+        }
+        let mut nfa = EpsilonNFA::new_unoptimized(
+            data.states.into_iter().collect(),
+            data.alphabets.into_iter().collect(),
+            transition_table,
+            data.start_states.into_iter().collect(),
+            data.final_states.into_iter().collect(),
+        )
+        .map_err(D::Error::custom)?;
+        for (from, to_states) in data.default_transitions {
+            for to in to_states {
+                nfa.add_default_transition(from, to);
+            }
+        }
+        if AUTO_OPTIMIZE {
+            nfa.minimize();
+        }
+        Ok(nfa)
+    }
+}
@@ -0,0 +1,544 @@
+use super::*;
+
+// Automaton independent view of the text format shared by `DFA` and `EpsilonNFA`:
+//
+//     # comments run to the end of the line
+//     states: q0, q1, q2
+//     alphabet: ε, a, b
+//     start: q0
+//     final: q2
+//     q0 a -> q1
+//     q0 ε -> q1, q2
+//     q1 else -> q2
+//
+// The `states` and `alphabet` headers have to come before the lines that use them. An alphabet
+// is a single character, or a quoted character such as `' '`, `','` or `'\n'` when it is
+// whitespace or one of `,:#'`. A bare `ε` is the epsilon transition and `'ε'` the character.
+// A range of characters joins its ends with `-`, as in `a-z` or `'\0'-' '`, and a transition
+// may read any range inside the alphabet. `else` is the default edge, reading the characters
+// outside the alphabet.
+pub(crate) struct Definition {
+    pub states: Vec<State>,
+    pub alphabets: Vec<Option<CharRange>>,
+    pub start_states: Vec<State>,
+    pub final_states: Vec<State>,
+    pub transitions: Vec<(State, Option<CharRange>, Vec<State>)>,
+    pub default_transitions: Vec<(State, Vec<State>)>,
+}
+
+const DEFAULT_LABEL: &str = "else";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum TokenKind {
+    Word(String),
+    Quoted(CharRange),
+    Comma,
+    Colon,
+}
+
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+impl Definition {
+    // With `deterministic` set, epsilon, several start states and several targets for the same
+    // state and alphabet are rejected where they appear
+    pub fn parse(text: &str, deterministic: bool) -> Result<Self, CustomError> {
+        let mut definition = Self {
+            states: Vec::new(),
+            alphabets: Vec::new(),
+            start_states: Vec::new(),
+            final_states: Vec::new(),
+            transitions: Vec::new(),
+            default_transitions: Vec::new(),
+        };
+        let mut seen_headers = HashSet::new();
+        let mut transition_keys: HashMap<(State, Option<CharRange>), usize> = HashMap::new();
+        let mut default_keys: HashMap<State, usize> = HashMap::new();
+        let mut lines_count = 0;
+        for (line_index, line) in text.lines().enumerate() {
+            lines_count = line_index + 1;
+            let error = |column: usize| CustomError::InvalidFormat {
+                line: line_index + 1,
+                column,
+            };
+            let tokens = tokenize(line).map_err(error)?;
+            let Some(first) = tokens.first() else {
+                continue;
+            };
+            let end_column = line.chars().count() + 1;
+            if tokens.get(1).map(|token| &token.kind) == Some(&TokenKind::Colon) {
+                let TokenKind::Word(header) = &first.kind else {
+                    return Err(error(first.column));
+                };
+                if !seen_headers.insert(header.clone()) {
+                    return Err(error(first.column));
+                }
+                let items = split_list(&tokens[2..], end_column).map_err(error)?;
+                match header.as_str() {
+                    "states" => {
+                        for token in items {
+                            let state = parse_state(token).map_err(error)?;
+                            if definition.states.contains(&state) {
+                                return Err(error(token.column));
+                            }
+                            definition.states.push(state);
+                        }
+                    }
+                    "alphabet" => {
+                        for token in items {
+                            let alphabet = parse_alphabet(token).map_err(error)?;
+                            if (deterministic && alphabet.is_none())
+                                || definition.alphabets.contains(&alphabet)
+                            {
+                                return Err(error(token.column));
+                            }
+                            definition.alphabets.push(alphabet);
+                        }
+                    }
+                    "start" | "final" => {
+                        let is_start = header == "start";
+                        for token in items {
+                            let state = parse_state(token).map_err(error)?;
+                            let states = if is_start {
+                                &mut definition.start_states
+                            } else {
+                                &mut definition.final_states
+                            };
+                            if !definition.states.contains(&state)
+                                || states.contains(&state)
+                                || (is_start && deterministic && !states.is_empty())
+                            {
+                                return Err(error(token.column));
+                            }
+                            states.push(state);
+                        }
+                    }
+                    _ => return Err(error(first.column)),
+                }
+                continue;
+            }
+            // state alphabet -> state, ...
+            let from = parse_state(first).map_err(error)?;
+            if !definition.states.contains(&from) {
+                return Err(error(first.column));
+            }
+            let alphabet_token = tokens.get(1).ok_or(error(end_column))?;
+            let is_default = alphabet_token.kind == TokenKind::Word(DEFAULT_LABEL.to_string());
+            let alphabet = if is_default {
+                None
+            } else {
+                parse_alphabet(alphabet_token).map_err(error)?
+            };
+            let is_declared = match alphabet {
+                _ if is_default => true,
+                Some(range) => {
+                    let alphabet_ranges =
+                        normalize_ranges(definition.alphabets.iter().copied().flatten());
+                    find_range(&alphabet_ranges, range.get_start())
+                        .is_some_and(|alphabet_range| range.get_end() <= alphabet_range.get_end())
+                }
+                None => definition.alphabets.contains(&None),
+            };
+            if !is_declared {
+                return Err(error(alphabet_token.column));
+            }
+            let arrow = tokens.get(2).ok_or(error(end_column))?;
+            if arrow.kind != TokenKind::Word("->".to_string()) {
+                return Err(error(arrow.column));
+            }
+            let items = split_list(&tokens[3..], end_column).map_err(error)?;
+            if items.is_empty() {
+                return Err(error(end_column));
+            }
+            let to_states = if is_default {
+                let index = match default_keys.entry(from) {
+                    Entry::Occupied(_) if deterministic => {
+                        return Err(error(alphabet_token.column))
+                    }
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        definition.default_transitions.push((from, Vec::new()));
+                        *entry.insert(definition.default_transitions.len() - 1)
+                    }
+                };
+                &mut definition.default_transitions[index].1
+            } else {
+                let index = match transition_keys.entry((from, alphabet)) {
+                    Entry::Occupied(_) if deterministic => {
+                        return Err(error(alphabet_token.column))
+                    }
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        definition.transitions.push((from, alphabet, Vec::new()));
+                        *entry.insert(definition.transitions.len() - 1)
+                    }
+                };
+                &mut definition.transitions[index].2
+            };
+            for token in items {
+                let to = parse_state(token).map_err(error)?;
+                if !definition.states.contains(&to) || (deterministic && !to_states.is_empty()) {
+                    return Err(error(token.column));
+                }
+                to_states.push(to);
+            }
+        }
+        if definition.start_states.is_empty() && (deterministic || !seen_headers.contains("start"))
+        {
+            return Err(CustomError::InvalidFormat {
+                line: lines_count + 1,
+                column: 1,
+            });
+        }
+        Ok(definition)
+    }
+
+    pub fn into_dfa(self) -> Result<DFA, CustomError> {
+        let alphabets = self.alphabets.into_iter().flatten().collect();
+        let mut transition_table = group_transitions(
+            &alphabets,
+            self.transitions
+                .into_iter()
+                .filter_map(|(from, alphabet, to)| Some(((from, alphabet?), to[0]))),
+        )?;
+        for (from, to) in self.default_transitions {
+            transition_table
+                .entry(from)
+                .or_default()
+                .set_default(Some(to[0]));
+        }
+        DFA::from_range_maps_unoptimized(
+            self.states.into_iter().collect(),
+            alphabets,
+            transition_table,
+            self.start_states[0],
+            self.final_states.into_iter().collect(),
+        )
+    }
+
+    pub fn into_epsilon_nfa(self) -> Result<EpsilonNFA, CustomError> {
+        let mut nfa = EpsilonNFA::new_unoptimized(
+            self.states.into_iter().collect(),
+            self.alphabets.into_iter().collect(),
+            self.transitions
+                .into_iter()
+                .map(|(from, alphabet, to)| ((from, alphabet), to.into_iter().collect()))
+                .collect(),
+            self.start_states.into_iter().collect(),
+            self.final_states.into_iter().collect(),
+        )?;
+        for (from, to_states) in self.default_transitions {
+            for to in to_states {
+                nfa.add_default_transition(from, to);
+            }
+        }
+        Ok(nfa)
+    }
+}
+
+impl From<&DFA> for Definition {
+    fn from(value: &DFA) -> Self {
+        Self {
+            states: value
+                .get_states()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            alphabets: value
+                .get_alphabets()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .map(Some)
+                .collect(),
+            start_states: vec![*value.get_start_state()],
+            final_states: value
+                .get_final_states()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            transitions: value
+                .get_transition_table()
+                .iter()
+                .flat_map(|(&from, transitions)| {
+                    transitions
+                        .get_ranges()
+                        .iter()
+                        .map(move |&(alphabet, to)| (from, Some(alphabet), vec![to]))
+                })
+                .sorted_unstable()
+                .collect(),
+            default_transitions: value
+                .get_transition_table()
+                .iter()
+                .filter_map(|(&from, transitions)| Some((from, vec![*transitions.get_default()?])))
+                .sorted_unstable()
+                .collect(),
+        }
+    }
+}
+
+impl From<&EpsilonNFA> for Definition {
+    fn from(value: &EpsilonNFA) -> Self {
+        Self {
+            states: value
+                .get_states()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            alphabets: value
+                .get_alphabets()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            start_states: value
+                .get_start_states()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            final_states: value
+                .get_final_states()
+                .iter()
+                .copied()
+                .sorted_unstable()
+                .collect(),
+            transitions: value
+                .get_transition_table()
+                .iter()
+                .flat_map(|(&from, transitions)| {
+                    transitions
+                        .get_ranges()
+                        .iter()
+                        .map(move |(alphabet, to)| (from, Some(*alphabet), to))
+                })
+                .chain(
+                    value
+                        .get_epsilon_transitions()
+                        .iter()
+                        .map(|(&from, to)| (from, None, to)),
+                )
+                .filter(|(_, _, to)| !to.is_empty())
+                .map(|(from, alphabet, to)| {
+                    (
+                        from,
+                        alphabet,
+                        to.iter().copied().sorted_unstable().collect(),
+                    )
+                })
+                .sorted_unstable()
+                .collect(),
+            default_transitions: value
+                .get_transition_table()
+                .iter()
+                .filter_map(|(&from, transitions)| {
+                    let to = transitions.get_default()?;
+                    Some((from, to.iter().copied().sorted_unstable().collect()))
+                })
+                .sorted_unstable()
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Definition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "states: {}", self.states.iter().join(", "))?;
+        writeln!(
+            f,
+            "alphabet: {}",
+            self.alphabets
+                .iter()
+                .map(|&alphabet| format_alphabet(alphabet))
+                .join(", ")
+        )?;
+        writeln!(f, "start: {}", self.start_states.iter().join(", "))?;
+        write!(f, "final: {}", self.final_states.iter().join(", "))?;
+        let transitions = self
+            .transitions
+            .iter()
+            .map(|(from, alphabet, to)| (from, format_alphabet(*alphabet), to))
+            .chain(
+                self.default_transitions
+                    .iter()
+                    .map(|(from, to)| (from, DEFAULT_LABEL.to_string(), to)),
+            )
+            .sorted_by_key(|&(from, _, _)| from);
+        for (from, alphabet, to) in transitions {
+            write!(f, "\n{from} {alphabet} -> {}", to.iter().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// Splits a line into tokens, returning the column of the offending character on failure
+fn tokenize(line: &str) -> Result<Vec<Token>, usize> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().zip(1..).peekable();
+    while let Some((ch, column)) = chars.next() {
+        let kind = match ch {
+            '#' => break,
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            '\'' => {
+                let start = parse_quoted(&mut chars).ok_or(column)?;
+                // A quoted range goes on with `-'` right after the first quote
+                let mut lookahead = chars.clone().map(|(ch, _)| ch);
+                if (lookahead.next(), lookahead.next()) == (Some('-'), Some('\'')) {
+                    chars.nth(1);
+                    let end = parse_quoted(&mut chars).ok_or(column)?;
+                    if end < start {
+                        return Err(column);
+                    }
+                    TokenKind::Quoted(CharRange::new(start, end))
+                } else {
+                    TokenKind::Quoted(CharRange::from(start))
+                }
+            }
+            _ if ch.is_whitespace() => continue,
+            _ => {
+                let mut word = ch.to_string();
+                while let Some(&(ch, _)) = chars.peek() {
+                    if ch.is_whitespace() || [',', ':', '#', '\''].contains(&ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                TokenKind::Word(word)
+            }
+        };
+        tokens.push(Token { kind, column });
+    }
+    Ok(tokens)
+}
+
+// Reads a quoted character after its opening quote
+fn parse_quoted(chars: &mut impl Iterator<Item = (char, usize)>) -> Option<char> {
+    let ch = match chars.next()? {
+        ('\\', _) => parse_escape(chars)?,
+        ('\'', _) => return None,
+        (ch, _) => ch,
+    };
+    (chars.next()?.0 == '\'').then_some(ch)
+}
+
+fn parse_escape(chars: &mut impl Iterator<Item = (char, usize)>) -> Option<char> {
+    match chars.next()?.0 {
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        '0' => Some('\0'),
+        '\\' => Some('\\'),
+        '\'' => Some('\''),
+        'u' => {
+            if chars.next()?.0 != '{' {
+                return None;
+            }
+            let digits: String = chars
+                .map(|(ch, _)| ch)
+                .take_while(|&ch| ch != '}')
+                .collect();
+            char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+        }
+        _ => None,
+    }
+}
+
+// Splits a comma separated list, allowing it to be empty
+fn split_list(tokens: &[Token], end_column: usize) -> Result<Vec<&Token>, usize> {
+    let mut items = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let is_comma = token.kind == TokenKind::Comma;
+        if is_comma != (index % 2 == 1) {
+            return Err(token.column);
+        }
+        if !is_comma {
+            items.push(token);
+        }
+    }
+    if tokens
+        .last()
+        .is_some_and(|token| token.kind == TokenKind::Comma)
+    {
+        return Err(end_column);
+    }
+    Ok(items)
+}
+
+fn parse_state(token: &Token) -> Result<State, usize> {
+    match &token.kind {
+        TokenKind::Word(word) => word
+            .strip_prefix(DEFAULT_STATE_SYMBOL)
+            .filter(|index| index.chars().all(|ch| ch.is_ascii_digit()))
+            .and_then(|index| index.parse::<u32>().ok())
+            .map(|index| State::new(index as usize))
+            .ok_or(token.column),
+        _ => Err(token.column),
+    }
+}
+
+fn parse_alphabet(token: &Token) -> Result<Option<CharRange>, usize> {
+    match &token.kind {
+        TokenKind::Quoted(range) => Ok(Some(*range)),
+        TokenKind::Word(word) if word == "ε" => Ok(None),
+        TokenKind::Word(word) => match word.chars().collect_vec()[..] {
+            [ch] => Ok(Some(CharRange::from(ch))),
+            [start, '-', end] if start <= end => Ok(Some(CharRange::new(start, end))),
+            _ => Err(token.column),
+        },
+        _ => Err(token.column),
+    }
+}
+
+fn format_alphabet(alphabet: Option<CharRange>) -> String {
+    let Some(range) = alphabet else {
+        return "ε".to_string();
+    };
+    if range.is_single() {
+        return format_char(range.get_start());
+    }
+    let ends = [range.get_start(), range.get_end()];
+    // Both ends are quoted as soon as one of them has to be
+    let [start, end] = if ends.iter().any(|&ch| format_char(ch).starts_with('\'')) {
+        ends.map(quote_char)
+    } else {
+        ends.map(format_char)
+    };
+    format!("{start}-{end}")
+}
+
+fn quote_char(ch: char) -> String {
+    let formatted = format_char(ch);
+    if formatted.starts_with('\'') {
+        formatted
+    } else {
+        format!("'{formatted}'")
+    }
+}
+
+fn format_char(ch: char) -> String {
+    match ch {
+        'ε' | ',' | ':' | '#' => format!("'{ch}'"),
+        ch if ch.is_whitespace() || !is_printable(ch) || ch == '\'' || ch == '\\' => {
+            let escaped = match ch {
+                '\n' => "\\n".to_string(),
+                '\r' => "\\r".to_string(),
+                '\t' => "\\t".to_string(),
+                '\0' => "\\0".to_string(),
+                '\\' => "\\\\".to_string(),
+                '\'' => "\\'".to_string(),
+                ' ' => " ".to_string(),
+                _ => format!("\\u{{{:x}}}", ch as u32),
+            };
+            format!("'{escaped}'")
+        }
+        ch => ch.to_string(),
+    }
+}
@@ -1,6 +1,6 @@
 use super::*;
 
-pub trait StringIndentation: ToString {
+pub(crate) trait StringIndentation: ToString {
     fn buffer(&self, size: usize) -> String {
         format!(
             "{}{}{}",
@@ -12,72 +12,46 @@ pub trait StringIndentation: ToString {
 
     fn center(&self, size: usize) -> String {
         let self_string = self.to_string();
-        self_string.buffer(size.checked_sub(self_string.len()).unwrap_or_default())
+        self_string.buffer(size.saturating_sub(self_string.len()))
     }
 }
 
 impl<T: ToString> StringIndentation for T {}
 
+/// A dense automaton state ID.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct State([Option<(char, usize)>; STATE_ARRAY_SIZE]);
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct State(u32);
 
 impl State {
-    pub fn new_empty() -> Self {
-        Self([None; STATE_ARRAY_SIZE])
+    pub fn new(index: usize) -> Self {
+        Self(u32::try_from(index).expect("State index does not fit in a u32"))
     }
 
-    pub fn new(symbol: char, index: usize) -> Self {
-        let mut state = Self::new_empty();
-        state.0[0] = Some((symbol, index));
-        state
-    }
-
-    pub fn get_index(self) -> Vec<(char, usize)> {
-        self.0.iter().filter_map(|&index| index).collect_vec()
-    }
-
-    pub fn concat(self, other: Self) -> Self {
-        let mut index = self.0;
-        index
-            .iter_mut()
-            .filter(|state| state.is_none())
-            .zip(other.0.iter().filter(|state| state.is_some()))
-            .for_each(|(name, &new_name)| *name = new_name);
-        Self(index)
+    pub fn get_index(self) -> usize {
+        self.0 as usize
     }
 }
 
 impl From<State> for usize {
     fn from(value: State) -> Self {
-        assert!(
-            value.get_index().len() < 2,
-            "Cannot convert {value} to usize"
-        );
-        value
-            .get_index()
-            .get(0)
-            .map(|(_, index)| *index)
-            .unwrap_or(usize::MAX)
+        value.get_index()
     }
 }
 
 impl From<usize> for State {
     fn from(value: usize) -> Self {
-        Self::new('q', value)
+        Self::new(value)
     }
 }
 
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut state_string = self
-            .get_index()
-            .into_iter()
-            .map(|(symbol, index)| format!("{symbol}{index}"))
-            .join("");
-        if state_string.is_empty() {
-            state_string += "epsilon";
-        }
-        write!(f, "{state_string}")
+        write!(f, "{DEFAULT_STATE_SYMBOL}{}", self.0)
     }
 }
 
@@ -87,11 +61,90 @@ impl fmt::Debug for State {
     }
 }
 
+/// Where a state produced by one of the automaton constructions came from.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum Provenance {
+    /// The sorted set of NFA states merged by the subset construction.
+    Subset(Vec<State>),
+    /// The pair of states of the two operands of a product construction, `None` being the
+    /// implicit trap of an operand whose alphabet lacks a character.
+    Product(Option<State>, Option<State>),
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Subset(states) => write!(f, "{{{}}}", states.iter().join(", ")),
+            Self::Product(state1, state2) => {
+                let [state1, state2] = [state1, state2]
+                    .map(|state| state.map_or("-".to_string(), |state| state.to_string()));
+                write!(f, "({state1}, {state2})")
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CustomError {
     InvalidDFAKeys,
     InvalidDFAFinalStates,
     NoDFAStartState,
     InvalidAlphabet,
     InvalidRegex,
+    /// A malformed automaton definition, at a 1-based line and column.
+    InvalidFormat {
+        line: usize,
+        column: usize,
+    },
+    /// A malformed serialized DFA, at the byte offset of the offending word.
+    InvalidBinaryFormat {
+        offset: usize,
+    },
+    /// A range too wide to write to JFLAP, which needs a transition for each of its characters.
+    UnsupportedJFFRange {
+        range: CharRange,
+    },
+    /// A default edge, which JFLAP has no way to write.
+    UnsupportedJFFDefault,
+    /// A match kind the engine cannot follow.
+    UnsupportedMatchKind,
 }
+
+impl fmt::Display for CustomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDFAKeys => write!(
+                f,
+                "the transition table needs exactly one entry to a state for every state and alphabet"
+            ),
+            Self::InvalidDFAFinalStates => write!(f, "the final states are not all states"),
+            Self::NoDFAStartState => write!(f, "the start state is not one of the states"),
+            Self::InvalidAlphabet => write!(f, "the ranges of the alphabet overlap"),
+            Self::InvalidRegex => write!(f, "invalid regular expression"),
+            Self::InvalidFormat { line, column } => {
+                write!(
+                    f,
+                    "invalid automaton definition at line {line}, column {column}"
+                )
+            }
+            Self::InvalidBinaryFormat { offset } => {
+                write!(f, "invalid serialized DFA at byte offset {offset}")
+            }
+            Self::UnsupportedJFFRange { range } => write!(
+                f,
+                "the range {range} is too wide for JFLAP, which needs a transition per character"
+            ),
+            Self::UnsupportedJFFDefault => write!(
+                f,
+                "JFLAP has no transition for the characters outside the alphabet"
+            ),
+            Self::UnsupportedMatchKind => write!(
+                f,
+                "leftmost-first matching needs the order of the alternatives, which only a Regex keeps"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CustomError {}